@@ -1,7 +1,14 @@
-use std::error::Error;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use rand::Rng;
 use rand::prelude::SliceRandom;
+use crate::data::error::DataError;
+use crate::ml::metrics;
+
+const NUM_FEATURES: usize = 57;
+
+/// Feature and target pairs, as stored in a `Dataset`.
+pub type Samples = [(Vec<f64>, Vec<f64>)];
 
 pub struct Dataset{
     pub train_data: Vec<(Vec<f64>, Vec<f64>)>,
@@ -13,25 +20,89 @@ impl Dataset {
         Self { train_data, test_data }
     }
 
-    pub fn load_data(path: &str, split_ratio: f64) -> Result<Self, Box<dyn Error>> {
-        let file = File::open(path).expect("Failed to open file");
-        let reader = BufReader::new(file);
-        let mut all_data: Vec<(Vec<f64>, Vec<f64>)> = Vec::new();
+    /// Loads spambase: 57 feature columns followed by the 0/1 label. Rows
+    /// are shuffled with `rng` before the split.
+    pub fn load_data(path: &str, split_ratio: f64, rng: &mut impl Rng) -> Result<Self, DataError> {
+        let rows = read_rows(path, Some(NUM_FEATURES + 1))?;
+        Self::from_rows(rows, 1, split_ratio, rng)
+    }
 
-        for line in reader.lines() {
-            let line = line?;
-            if line.trim().is_empty() {
-                continue;
-            }
-            let values: Vec<f64> = line.split(',').map(|s| s.parse().unwrap()).collect();
-            let features = values[0..57].to_vec();
-            let target = vec![values[57]];
-            all_data.push((features, target));
+    /// Loads a headerless numeric CSV whose last `num_targets` columns are
+    /// the targets, e.g. several continuous values for regression. With
+    /// `num_targets` 0 every column is a feature and the targets are empty,
+    /// as used by `Autoencoder`.
+    pub fn load_csv(path: &str, num_targets: usize, split_ratio: f64, rng: &mut impl Rng) -> Result<Self, DataError> {
+        let rows = read_rows(path, None)?;
+        if let Some(row) = rows.first() && row.len() <= num_targets {
+            return Err(DataError::RowLength {
+                path: path.to_string(),
+                line: 1,
+                expected: num_targets + 1,
+                found: row.len(),
+            });
+        }
+        Self::from_rows(rows, num_targets, split_ratio, rng)
+    }
+
+    /// Splits each row into features and its last `num_targets` values,
+    /// shuffles with `rng` and keeps `split_ratio` of the rows for training.
+    /// Every row must have the same width, with at least one feature.
+    pub fn from_rows(
+        rows: Vec<Vec<f64>>,
+        num_targets: usize,
+        split_ratio: f64,
+        rng: &mut impl Rng,
+    ) -> Result<Self, DataError> {
+        let columns = rows.first().map_or(0, Vec::len);
+        if let Some((row, values)) = rows.iter().enumerate().find(|(_, values)| values.len() != columns) {
+            return Err(DataError::RowWidth { row, expected: columns, found: values.len() });
         }
+        if !rows.is_empty() && columns <= num_targets {
+            return Err(DataError::NoFeatures { columns, num_targets });
+        }
+
+        let mut all_data: Vec<(Vec<f64>, Vec<f64>)> = rows
+            .into_iter()
+            .map(|mut values| {
+                let target = values.split_off(values.len() - num_targets);
+                (values, target)
+            })
+            .collect();
+
+        shuffle_data(&mut all_data, rng);
+        let (train_data, test_data) = split_data(&all_data, split_ratio);
+        Ok(Dataset::new(train_data.to_vec(), test_data.to_vec()))
+    }
+
+    pub fn num_features(&self) -> usize {
+        self.train_data.first().map_or(0, |(features, _)| features.len())
+    }
+
+    pub fn num_targets(&self) -> usize {
+        self.train_data.first().map_or(0, |(_, target)| target.len())
+    }
+
+    /// Splits `train_data` into a training part and a held-out validation
+    /// part made of its last `validation_ratio` rows.
+    pub fn validation_split(&self, validation_ratio: f64) -> (&Samples, &Samples) {
+        let split_idx = (self.train_data.len() as f64 * (1.0 - validation_ratio)) as usize;
+        self.train_data.split_at(split_idx)
+    }
 
-        shuffle_data(&mut all_data);
-        let (train_data, test_data) = split_data(all_data, split_ratio);
-        Ok(Dataset::new(train_data, test_data))
+    /// "Balanced" class weights from the training labels,
+    /// `samples / (classes * class_count)`, so every class contributes
+    /// equally to the loss.
+    pub fn balanced_class_weights(&self) -> Vec<f64> {
+        let classes = self.num_targets().max(2);
+        let mut counts = vec![0usize; classes];
+        for (_, target) in &self.train_data {
+            counts[metrics::predicted_class(target)] += 1;
+        }
+        let samples = self.train_data.len() as f64;
+        counts
+            .iter()
+            .map(|&count| if count > 0 { samples / (classes as f64 * count as f64) } else { 0.0 })
+            .collect()
     }
 
     pub fn normalize(&mut self) {
@@ -83,19 +154,70 @@ fn apply_normalization(
     }
 }
 
-fn shuffle_data(data: &mut Vec<(Vec<f64>, Vec<f64>)>) {
-    let mut rng = rand::rng();
-    data.shuffle(&mut rng);
+/// Feature names from a UCI `.names` file: every `name: continuous.` line
+/// outside the `|` comments, in column order.
+pub fn load_feature_names(path: &str) -> Result<Vec<String>, DataError> {
+    let contents = std::fs::read_to_string(path).map_err(|source| DataError::Io { path: path.to_string(), source })?;
+    Ok(contents
+        .lines()
+        .filter(|line| !line.starts_with('|'))
+        .filter_map(|line| line.rsplit_once(':'))
+        .filter(|(_, kind)| kind.trim() == "continuous.")
+        .map(|(name, _)| name.trim().to_string())
+        .collect())
+}
+
+/// Reads every non-empty line as numbers. Rows must all have
+/// `expected_columns` cells, or as many as the first row when `None`.
+fn read_rows(path: &str, expected_columns: Option<usize>) -> Result<Vec<Vec<f64>>, DataError> {
+    let io_error = |source| DataError::Io { path: path.to_string(), source };
+    let file = File::open(path).map_err(io_error)?;
+    let reader = BufReader::new(file);
+    let mut rows: Vec<Vec<f64>> = Vec::new();
+    let mut expected_columns = expected_columns;
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(io_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let values = parse_row(path, index + 1, &line)?;
+        let expected = *expected_columns.get_or_insert(values.len());
+        if values.len() != expected {
+            return Err(DataError::RowLength {
+                path: path.to_string(),
+                line: index + 1,
+                expected,
+                found: values.len(),
+            });
+        }
+        rows.push(values);
+    }
+
+    Ok(rows)
+}
+
+fn parse_row(path: &str, line: usize, row: &str) -> Result<Vec<f64>, DataError> {
+    row.split(',')
+        .enumerate()
+        .map(|(index, cell)| {
+            cell.trim().parse().map_err(|_| DataError::Parse {
+                path: path.to_string(),
+                line,
+                column: index + 1,
+                value: cell.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn shuffle_data(data: &mut Vec<(Vec<f64>, Vec<f64>)>, rng: &mut impl Rng) {
+    data.shuffle(rng);
 }
 
-fn split_data(
-    data: Vec<(Vec<f64>, Vec<f64>)>,
-    split_ratio: f64,
-) -> (Vec<(Vec<f64>, Vec<f64>)>, Vec<(Vec<f64>, Vec<f64>)>) {
+fn split_data(data: &Samples, split_ratio: f64) -> (&Samples, &Samples) {
     let split_idx = (data.len() as f64 * split_ratio) as usize;
-    let train = data[0..split_idx].to_vec();
-    let test = data[split_idx..].to_vec();
-    (train, test)
+    data.split_at(split_idx)
 }
 
 #[cfg(test)]
@@ -104,7 +226,7 @@ mod tests {
 
     #[test]
     fn test_load_spambase() {
-        let dataset = Dataset::load_data("spambase/spambase.data", 0.8).unwrap();
+        let dataset = Dataset::load_data("spambase/spambase.data", 0.8, &mut rand::rng()).unwrap();
         println!("Train: {}", dataset.train_data.len());
         println!("Test: {}", dataset.test_data.len());
 
@@ -112,4 +234,78 @@ mod tests {
         assert_eq!(dataset.train_data[0].0.len(), 57);
         assert_eq!(dataset.train_data[0].1.len(), 1);
     }
+
+    #[test]
+    fn test_load_missing_file() {
+        let error = Dataset::load_data("spambase/missing.data", 0.8, &mut rand::rng()).err().unwrap();
+        assert!(matches!(error, DataError::Io { .. }));
+    }
+
+    #[test]
+    fn test_parse_row_reports_column() {
+        let error = parse_row("test.csv", 4, "1.0,2.0,abc").unwrap_err();
+        match error {
+            DataError::Parse { line, column, value, .. } => {
+                assert_eq!((line, column, value.as_str()), (4, 3, "abc"));
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn test_load_csv_with_multiple_targets() {
+        let path = std::env::temp_dir().join("dataset_multi_target.csv");
+        std::fs::write(&path, "1,2,3,4\n5,6,7,8\n\n9,10,11,12\n").unwrap();
+
+        let dataset = Dataset::load_csv(path.to_str().unwrap(), 2, 1.0, &mut rand::rng()).unwrap();
+        assert_eq!(dataset.train_data.len(), 3);
+        assert_eq!(dataset.num_features(), 2);
+        assert_eq!(dataset.num_targets(), 2);
+        for (features, target) in &dataset.train_data {
+            assert_eq!(target[0], features[0] + 2.0);
+        }
+
+        std::fs::write(&path, "1,2,3\n4,5\n").unwrap();
+        let error = Dataset::load_csv(path.to_str().unwrap(), 1, 1.0, &mut rand::rng()).err().unwrap();
+        assert!(matches!(error, DataError::RowLength { line: 2, expected: 3, found: 2, .. }));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_from_rows_checks_widths() {
+        let rows = vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]];
+        let dataset = Dataset::from_rows(rows, 1, 1.0, &mut rand::rng()).unwrap();
+        assert_eq!((dataset.num_features(), dataset.num_targets()), (2, 1));
+
+        let rows = vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0]];
+        let error = Dataset::from_rows(rows, 1, 1.0, &mut rand::rng()).err().unwrap();
+        assert!(matches!(error, DataError::RowWidth { row: 1, expected: 3, found: 2 }));
+
+        let rows = vec![vec![1.0, 2.0], vec![3.0, 4.0]];
+        let error = Dataset::from_rows(rows, 2, 1.0, &mut rand::rng()).err().unwrap();
+        assert!(matches!(error, DataError::NoFeatures { columns: 2, num_targets: 2 }));
+    }
+
+    #[test]
+    fn test_balanced_class_weights() {
+        let train_data = vec![
+            (vec![0.0], vec![1.0]),
+            (vec![0.0], vec![0.0]),
+            (vec![0.0], vec![0.0]),
+            (vec![0.0], vec![0.0]),
+        ];
+        let dataset = Dataset::new(train_data, Vec::new());
+
+        assert_eq!(dataset.balanced_class_weights(), vec![4.0 / 6.0, 2.0]);
+    }
+
+    #[test]
+    fn test_load_spambase_feature_names() {
+        let names = load_feature_names("spambase/spambase.names").unwrap();
+
+        assert_eq!(names.len(), 57);
+        assert_eq!(names[0], "word_freq_make");
+        assert_eq!(names[48], "char_freq_;");
+        assert_eq!(names[56], "capital_run_length_total");
+    }
 }
\ No newline at end of file
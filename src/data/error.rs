@@ -0,0 +1,66 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum DataError {
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    /// A cell that is not a number. `line` and `column` are 1-based.
+    Parse {
+        path: String,
+        line: usize,
+        column: usize,
+        value: String,
+    },
+    /// A row with the wrong number of cells.
+    RowLength {
+        path: String,
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// An in-memory row whose width differs from the first row's. `row` is
+    /// 0-based.
+    RowWidth {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// Rows with no columns left for features after the targets.
+    NoFeatures {
+        columns: usize,
+        num_targets: usize,
+    },
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataError::Io { path, source } => write!(f, "{}: {}", path, source),
+            DataError::Parse { path, line, column, value } => {
+                write!(f, "{}:{}:{}: invalid number {:?}", path, line, column, value)
+            }
+            DataError::RowLength { path, line, expected, found } => {
+                write!(f, "{}:{}: expected {} columns, found {}", path, line, expected, found)
+            }
+            DataError::RowWidth { row, expected, found } => {
+                write!(f, "row {}: expected {} columns, found {}", row, expected, found)
+            }
+            DataError::NoFeatures { columns, num_targets } => {
+                write!(f, "{} columns leave no features besides {} targets", columns, num_targets)
+            }
+        }
+    }
+}
+
+impl Error for DataError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DataError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
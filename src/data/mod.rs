@@ -1 +1,2 @@
-pub mod dataset;
\ No newline at end of file
+pub mod dataset;
+pub mod error;
\ No newline at end of file
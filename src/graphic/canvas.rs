@@ -1,22 +1,152 @@
 use raylib::prelude::*;
-use rand::prelude::*;
+use std::error::Error;
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
-use crate::ml::perceptron::Perceptron;
+use std::time::{Duration, Instant};
+use rand::Rng;
+use rand::seq::SliceRandom;
 use crate::ml::layer::Layer;
 use crate::graphic::model_visualisation::ModelVisualisation;
 use crate::graphic::camera::Camera;
+use crate::graphic::loss::LossVisualisation;
 use crate::ml::model::Model;
+use crate::ml::error::ModelError;
+use crate::ml::clipping::GradientClipping;
+use crate::ml::loss::{ClassWeighting, Loss};
+use crate::ml::metrics;
 use crate::ml::activation::Activation;
-use crate::data::dataset::Dataset;
+use crate::ml::seed::{self, RunSeed};
+use crate::ml::checkpoint::{Checkpoint, CheckpointManager};
+use crate::ml::persistence;
+use crate::ml::history::{EpochRecord, TrainingHistory};
+use crate::ml::predictor::Predictor;
+use crate::ml::training::{ParameterGroup, TrainingContext};
+use crate::ml::evolution::{self, Evolution, Selection};
+use crate::ml::lbfgs::{Lbfgs, StopReason};
+use crate::ml::pruning::{self, FineTune};
+use crate::ml::quantization::{self, QuantizedModel, ScaleGranularity};
+use crate::ml::search::{self, Ranking, Search, SearchSpace, Strategy};
+use crate::ml::cross_validation::CrossValidation;
+use crate::ml::ensemble::{Combination, Ensemble};
+use crate::ml::attribution;
+use crate::ml::autoencoder::{self, Autoencoder};
+use crate::ml::baselines::{self, BernoulliNaiveBayes, GaussianNaiveBayes, KNearestNeighbours, LogisticRegression};
+use crate::data::dataset::{self, Dataset};
+
+const CHECKPOINT_DIR: &str = "checkpoints";
+/// Kept apart so that no trainer resumes another one's run.
+const EVOLUTION_CHECKPOINT_DIR: &str = "checkpoints/evolution";
+const LBFGS_CHECKPOINT_DIR: &str = "checkpoints/lbfgs";
+const LEARNING_RATE: f64 = 0.01;
+const MAX_EPOCHS: usize = 100;
+const MAX_GENERATIONS: usize = 200;
+const MAX_LBFGS_ITERATIONS: usize = 200;
+const POPULATION_SIZE: usize = 30;
+/// Fittest individuals copied unchanged into each generation.
+const ELITISM: usize = 1;
+const CROSSOVER_RATE: f64 = 0.5;
+/// Share of a child's parameters perturbed, and the standard deviation of
+/// the noise.
+const MUTATION_RATE: f64 = 0.1;
+const MUTATION_SCALE: f64 = 0.1;
+/// `tournament` (the default) or `elite`, how evolution picks parents.
+const SELECTION_VAR: &str = "SELECTION";
+/// `loss` (the default) or `accuracy`, what evolution maximizes on the
+/// training part.
+const FITNESS_VAR: &str = "FITNESS";
+/// Global gradient norm above which a step is scaled down, so that a
+/// single outlier email cannot throw the weights off.
+const MAX_GRADIENT_NORM: f64 = 5.0;
+/// Bound on each gradient entry when clipping by value.
+const MAX_GRADIENT_VALUE: f64 = 1.0;
+/// `norm` (the default), `value` or `none`, how step gradients are clipped.
+const CLIPPING_VAR: &str = "CLIPPING";
+/// Share of the training split held out for the validation metrics that
+/// pick the best checkpoint. The test split is only used for the final
+/// report.
+const VALIDATION_RATIO: f64 = 0.1;
+/// Set to `evolution` to train with the genetic algorithm or to `lbfgs` for
+/// full-batch L-BFGS instead of backpropagation.
+const TRAINER_VAR: &str = "TRAINER";
+/// Set to `1` to also record the loss and gradient norm of every
+/// backpropagation step, exported as `batches.csv`. Steps are not
+/// checkpointed, so a resumed run only has those since it resumed.
+const BATCH_HISTORY_VAR: &str = "BATCH_HISTORY";
+/// Comma-separated list of extra reports printed after training, e.g.
+/// `pruning,quantization,search,cross_validation,ensemble,attribution,regression,transfer,anomaly`.
+const REPORTS_VAR: &str = "REPORTS";
+const PRUNING_LEVELS: [f64; 5] = [0.0, 0.5, 0.7, 0.9, 0.95];
+/// Training rows used to fix the int8 input scales.
+const CALIBRATION_SAMPLES: usize = 200;
+/// `grid`, `random`, `halving` or `hyperband` (the default).
+const SEARCH_STRATEGY_VAR: &str = "SEARCH_STRATEGY";
+/// `loss` (the default) or `accuracy`, the validation metric that ranks
+/// search trials.
+const SEARCH_RANKING_VAR: &str = "SEARCH_RANKING";
+/// Longest training a search trial gets.
+const SEARCH_MAX_EPOCHS: usize = 9;
+const CROSS_VALIDATION_FOLDS: usize = 5;
+const CROSS_VALIDATION_EPOCHS: usize = 10;
+const ENSEMBLE_MEMBERS: usize = 5;
+const ENSEMBLE_EPOCHS: usize = 10;
+/// Features listed per attribution ranking.
+const TOP_FEATURES: usize = 10;
+/// The capital-letter run statistics at the end of each spambase row, fitted
+/// by the regression report.
+const REGRESSION_TARGETS: usize = 3;
+const REGRESSION_EPOCHS: usize = 10;
+const TRANSFER_EPOCHS: usize = 5;
+const AUTOENCODER_EPOCHS: usize = 10;
+/// Share of ham whose reconstruction error stays under the anomaly
+/// threshold.
+const ANOMALY_QUANTILE: f64 = 0.95;
+/// Learning-rate factor per layer below the output when fine-tuning the
+/// whole network.
+const LAYERWISE_DECAY: f64 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Trainer {
+    Backpropagation,
+    Evolution,
+    Lbfgs,
+}
+
+impl Trainer {
+    fn from_env() -> Self {
+        match std::env::var(TRAINER_VAR).as_deref().map(str::trim) {
+            Ok("evolution") => Trainer::Evolution,
+            Ok("lbfgs") => Trainer::Lbfgs,
+            _ => Trainer::Backpropagation,
+        }
+    }
+
+    fn checkpoint_dir(self) -> &'static str {
+        match self {
+            Trainer::Backpropagation => CHECKPOINT_DIR,
+            Trainer::Evolution => EVOLUTION_CHECKPOINT_DIR,
+            Trainer::Lbfgs => LBFGS_CHECKPOINT_DIR,
+        }
+    }
+
+    /// Epochs, generations or iterations a run lasts.
+    fn max_epochs(self) -> usize {
+        match self {
+            Trainer::Backpropagation => MAX_EPOCHS,
+            Trainer::Evolution => MAX_GENERATIONS,
+            Trainer::Lbfgs => MAX_LBFGS_ITERATIONS,
+        }
+    }
+}
 
 struct TrainingState {
     model: Arc<Mutex<Model>>,
     dataset: Arc<Dataset>,
     epoch: Arc<AtomicU32>,
-    loss: Arc<Mutex<f64>>,
+    history: Arc<Mutex<TrainingHistory>>,
     is_running: Arc<AtomicBool>,
 }
 
@@ -25,6 +155,7 @@ pub struct Canvas {
     height: i32,
     color: Color,
     model_visualisation: ModelVisualisation,
+    loss_visualisation: LossVisualisation,
     training_state: TrainingState,
     training_thread: Option<JoinHandle<()>>,
     last_update_epoch: u32,
@@ -33,47 +164,89 @@ pub struct Canvas {
 }
 
 impl Canvas {
-    pub fn new(width: i32, height: i32, color: Color) -> Self {
-        let (model, dataset) = create_spam_classifier();
+    pub fn new(width: i32, height: i32, color: Color) -> Result<Self, Box<dyn Error>> {
+        let trainer = Trainer::from_env();
+        let (directory, max_epochs) = (trainer.checkpoint_dir(), trainer.max_epochs());
+        // Each seed trains in its own directory, so starting a new run never
+        // touches the checkpoints of an earlier one
+        let seed = RunSeed::requested()
+            .or_else(|| unfinished_run(directory, max_epochs))
+            .unwrap_or_else(RunSeed::random);
+        println!("Run seed: {} (set {} to reproduce)", seed, seed::SEED_VAR);
+        let checkpoints = CheckpointManager::new(run_directory(directory, seed)).with_interval(5).with_keep_last(3);
+        let resumed = match checkpoints.latest() {
+            Ok(resumed) => resumed,
+            Err(error) => {
+                println!("Ignoring checkpoints: {}", error);
+                None
+            }
+        };
+        let (model, dataset) = create_spam_classifier(seed)?;
+        let progress = match resumed {
+            Some(checkpoint) if checkpoint.epoch >= max_epochs => {
+                println!("Run {} already finished {} epochs, set {} to start another", seed, checkpoint.epoch, seed::SEED_VAR);
+                checkpoint
+            }
+            Some(checkpoint) => {
+                println!("Resuming from epoch {}", checkpoint.epoch);
+                checkpoint
+            }
+            None => Checkpoint { model, seed, epoch: 0, learning_rate: LEARNING_RATE, history: TrainingHistory::new() },
+        };
+        let model = progress.model.clone();
+        model.summary().print();
         let model_visualisation = ModelVisualisation::new(model.clone());
+        let mut loss_visualisation = LossVisualisation::new(width - 420, height - 220, 400, 200);
+        loss_visualisation.update(&progress.history);
         
         let camera = Camera::new(width, height);
         
         let shared_model = Arc::new(Mutex::new(model));
         let shared_dataset = Arc::new(dataset);
-        let shared_epoch = Arc::new(AtomicU32::new(0));
-        let shared_loss = Arc::new(Mutex::new(0.0));
+        let shared_epoch = Arc::new(AtomicU32::new(progress.epoch as u32));
+        let shared_history = Arc::new(Mutex::new(progress.history.clone()));
         let shared_running = Arc::new(AtomicBool::new(true));
         
         let training_state = TrainingState {
             model: shared_model.clone(),
             dataset: shared_dataset.clone(),
             epoch: shared_epoch.clone(),
-            loss: shared_loss.clone(),
+            history: shared_history.clone(),
             is_running: shared_running.clone(),
         };
         
         let thread_model = shared_model.clone();
         let thread_dataset = shared_dataset.clone();
         let thread_epoch = shared_epoch.clone();
-        let thread_loss = shared_loss.clone();
+        let thread_history = shared_history.clone();
         let thread_running = shared_running.clone();
         
         let training_thread = thread::spawn(move || {
-            Self::training_loop(thread_running, thread_model, thread_dataset, thread_epoch, thread_loss);
+            match trainer {
+                Trainer::Backpropagation => Self::training_loop(
+                    thread_running, thread_model, thread_dataset, thread_epoch, thread_history, progress, checkpoints,
+                ),
+                Trainer::Evolution => Self::evolution_loop(
+                    thread_running, thread_model, thread_dataset, thread_epoch, thread_history, progress, checkpoints,
+                ),
+                Trainer::Lbfgs => Self::lbfgs_loop(
+                    thread_running, thread_model, thread_dataset, thread_epoch, thread_history, progress, checkpoints,
+                ),
+            }
         });
         
-        Self { 
+        Ok(Self { 
             width, 
             height, 
             color,
             model_visualisation,
+            loss_visualisation,
             training_state,
             training_thread: Some(training_thread),
             last_update_epoch: 0,
             update_interval: 10,
             camera,
-        }
+        })
     }
 
     fn training_loop(
@@ -81,31 +254,240 @@ impl Canvas {
         thread_model: Arc<Mutex<Model>>,
         thread_dataset: Arc<Dataset>,
         thread_epoch: Arc<AtomicU32>,
-        thread_loss: Arc<Mutex<f64>>,
+        thread_history: Arc<Mutex<TrainingHistory>>,
+        mut progress: Checkpoint,
+        checkpoints: CheckpointManager,
     ) {
-        let mut current_epoch = 0u32;
+        // The thread trains its own copy and only publishes the weights, so
+        // that drawing never waits for an epoch to finish
+        let mut context = TrainingContext::new();
+        let mut train_data = Vec::new();
+        let (train_part, validation_data) = thread_dataset.validation_split(VALIDATION_RATIO);
+        let first_epoch = progress.epoch;
+        if std::env::var(BATCH_HISTORY_VAR).is_ok_and(|value| value.trim() == "1") {
+            progress.history = std::mem::take(&mut progress.history).with_batches();
+        }
         
-        while thread_running.load(Ordering::Relaxed) && current_epoch < 100 {
-            let loss = {
-                let mut model = thread_model.lock().unwrap();
-                model.train_epoch(&thread_dataset.train_data, 0.01)
+        while thread_running.load(Ordering::Relaxed) && progress.epoch < MAX_EPOCHS {
+            // Reshuffle from the original order so that an epoch's order
+            // only depends on the seed and the epoch number
+            train_data.clear();
+            train_data.extend_from_slice(train_part);
+            train_data.shuffle(&mut progress.seed.rng("shuffle", progress.epoch as u64));
+            let started = Instant::now();
+            let mut gradient_norms = 0.0;
+            let mut steps = 0;
+            let history = &mut progress.history;
+            let result = progress
+                .model
+                .try_train_epoch_with(&mut context, &train_data, progress.learning_rate, progress.epoch, |batch, step| {
+                    history.record_batch(progress.epoch + 1, batch, step);
+                    gradient_norms += step.gradient_norm;
+                    steps += 1;
+                })
+                .map(|loss| {
+                    (loss, progress.model.mean_loss(validation_data), progress.model.evaluate(validation_data).accuracy)
+                });
+
+            let (loss, validation_loss, validation_accuracy) = match result {
+                Ok(result) => result,
+                Err(ModelError::Diverged(error)) => {
+                    println!("Training diverged: {}", error);
+                    *thread_model.lock().unwrap() = *error.last_finite;
+                    thread_running.store(false, Ordering::Relaxed);
+                    return;
+                }
+                Err(error) => {
+                    println!("Training stopped: {}", error);
+                    thread_running.store(false, Ordering::Relaxed);
+                    return;
+                }
             };
             
-            current_epoch += 1;
-            thread_epoch.store(current_epoch, Ordering::Relaxed);
-            *thread_loss.lock().unwrap() = loss;
+            *thread_model.lock().unwrap() = progress.model.clone();
+            progress.epoch += 1;
+            let record = EpochRecord {
+                epoch: progress.epoch,
+                train_loss: loss,
+                validation_loss: Some(validation_loss),
+                train_accuracy: None,
+                validation_accuracy: Some(validation_accuracy),
+                learning_rate: progress.learning_rate,
+                gradient_norm: gradient_norms / steps.max(1) as f64,
+                seconds: started.elapsed().as_secs_f64(),
+            };
+            thread_history.lock().unwrap().record_epoch(record.clone());
+            progress.history.record_epoch(record);
+            thread_epoch.store(progress.epoch as u32, Ordering::Relaxed);
             
-            if current_epoch % 10 == 0 {
-                println!("Epoch {}: loss = {:.4}", current_epoch, loss);
+            if progress.epoch % 10 == 0 {
+                println!("Epoch {}: loss = {:.4}", progress.epoch, loss);
+            }
+
+            if checkpoints.is_due(progress.epoch)
+                && let Err(error) = checkpoints.save(&progress)
+            {
+                println!("Failed to save checkpoint: {}", error);
             }
             
             thread::sleep(Duration::from_millis(1));
         }
         
+        finish_run(&progress, first_epoch, &checkpoints, &thread_dataset);
+
         thread_running.store(false, Ordering::Relaxed);
         println!("Training complete!");
     }
 
+    /// Gradient-free alternative to `training_loop`. Each generation counts
+    /// as an epoch: the best individual becomes the displayed model and its
+    /// scores go into the same history.
+    ///
+    /// Checkpoints hold the best individual only, so a resumed run regrows
+    /// its population around it (see `Evolution::resume`) rather than
+    /// continuing exactly where an uninterrupted run would be.
+    fn evolution_loop(
+        thread_running: Arc<AtomicBool>,
+        thread_model: Arc<Mutex<Model>>,
+        thread_dataset: Arc<Dataset>,
+        thread_epoch: Arc<AtomicU32>,
+        thread_history: Arc<Mutex<TrainingHistory>>,
+        mut progress: Checkpoint,
+        checkpoints: CheckpointManager,
+    ) {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let evolution = Evolution::new()
+            .with_population_size(POPULATION_SIZE)
+            .with_selection(evolution_selection())
+            .with_elitism(ELITISM)
+            .with_crossover_rate(CROSSOVER_RATE)
+            .with_mutation(MUTATION_RATE, MUTATION_SCALE)
+            .with_threads(threads);
+        let (train_part, validation_data) = thread_dataset.validation_split(VALIDATION_RATIO);
+        let fitness: Box<dyn Fn(&Model) -> f64 + Sync> = match std::env::var(FITNESS_VAR).as_deref().map(str::trim) {
+            Ok("accuracy") => Box::new(evolution::accuracy(train_part)),
+            _ => Box::new(evolution::negative_loss(train_part)),
+        };
+        let mut rng = progress.seed.rng("population", progress.epoch as u64);
+        let mut population = evolution.resume(&progress.model, progress.epoch, &mut rng);
+        let first_epoch = progress.epoch;
+
+        while thread_running.load(Ordering::Relaxed) && progress.epoch < MAX_GENERATIONS {
+            let started = Instant::now();
+            evolution.evaluate(&mut population, &fitness);
+            let best = population.best();
+            // The fitness is not a loss when it is accuracy
+            let train_loss = best.mean_loss(train_part);
+
+            progress.epoch += 1;
+            let record = EpochRecord {
+                epoch: progress.epoch,
+                train_loss,
+                validation_loss: Some(best.mean_loss(validation_data)),
+                train_accuracy: None,
+                validation_accuracy: Some(best.evaluate(validation_data).accuracy),
+                // Mutation noise stands in for the step size; there are no gradients
+                learning_rate: evolution.mutation_scale,
+                gradient_norm: 0.0,
+                seconds: started.elapsed().as_secs_f64(),
+            };
+            progress.model = best.clone();
+            *thread_model.lock().unwrap() = best.clone();
+            thread_history.lock().unwrap().record_epoch(record.clone());
+            progress.history.record_epoch(record);
+            thread_epoch.store(progress.epoch as u32, Ordering::Relaxed);
+
+            if progress.epoch.is_multiple_of(10) {
+                let stats = population.fitness_stats();
+                println!(
+                    "Generation {}: loss = {:.4}, fitness {:.4} ± {:.4}",
+                    progress.epoch, train_loss, stats.mean, stats.std
+                );
+            }
+
+            if checkpoints.is_due(progress.epoch)
+                && let Err(error) = checkpoints.save(&progress)
+            {
+                println!("Failed to save checkpoint: {}", error);
+            }
+
+            population = evolution.next_generation(&population, &mut progress.seed.rng("evolution", progress.epoch as u64));
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        finish_run(&progress, first_epoch, &checkpoints, &thread_dataset);
+
+        thread_running.store(false, Ordering::Relaxed);
+        println!("Evolution complete!");
+    }
+
+    /// Full-batch L-BFGS alternative to `training_loop`. Each iteration
+    /// counts as an epoch, with the line search step as its learning rate.
+    ///
+    /// Checkpoints hold the weights only, so a resumed run starts a fresh
+    /// curvature history from them and takes a few iterations to catch up.
+    fn lbfgs_loop(
+        thread_running: Arc<AtomicBool>,
+        thread_model: Arc<Mutex<Model>>,
+        thread_dataset: Arc<Dataset>,
+        thread_epoch: Arc<AtomicU32>,
+        thread_history: Arc<Mutex<TrainingHistory>>,
+        mut progress: Checkpoint,
+        checkpoints: CheckpointManager,
+    ) {
+        let (train_part, validation_data) = thread_dataset.validation_split(VALIDATION_RATIO);
+        let first_epoch = progress.epoch;
+
+        if progress.epoch < MAX_LBFGS_ITERATIONS {
+            let lbfgs = Lbfgs::new().with_max_iterations(MAX_LBFGS_ITERATIONS - progress.epoch);
+            let mut model = progress.model.clone();
+            let mut started = Instant::now();
+            let report = lbfgs.minimize_with(&mut model, train_part, |iteration, current| {
+                progress.epoch = first_epoch + iteration.iteration;
+                let record = EpochRecord {
+                    epoch: progress.epoch,
+                    // Includes the weight decay penalty the optimizer minimizes
+                    train_loss: iteration.loss,
+                    validation_loss: Some(current.mean_loss(validation_data)),
+                    train_accuracy: None,
+                    validation_accuracy: Some(current.evaluate(validation_data).accuracy),
+                    learning_rate: iteration.step_size,
+                    gradient_norm: iteration.gradient_norm,
+                    seconds: started.elapsed().as_secs_f64(),
+                };
+                progress.model = current.clone();
+                *thread_model.lock().unwrap() = current.clone();
+                thread_history.lock().unwrap().record_epoch(record.clone());
+                progress.history.record_epoch(record);
+                thread_epoch.store(progress.epoch as u32, Ordering::Relaxed);
+
+                if progress.epoch.is_multiple_of(10) {
+                    println!("Iteration {}: loss = {:.4}", progress.epoch, iteration.loss);
+                }
+
+                if checkpoints.is_due(progress.epoch)
+                    && let Err(error) = checkpoints.save(&progress)
+                {
+                    println!("Failed to save checkpoint: {}", error);
+                }
+
+                started = Instant::now();
+                if thread_running.load(Ordering::Relaxed) { ControlFlow::Continue(()) } else { ControlFlow::Break(()) }
+            });
+            if report.stop != StopReason::Interrupted {
+                println!(
+                    "L-BFGS stopped after {} iterations ({:?}): loss = {:.4}, largest gradient = {:.2e}, {} evaluations",
+                    report.iterations, report.stop, report.loss, report.gradient_norm, report.evaluations,
+                );
+            }
+        }
+
+        finish_run(&progress, first_epoch, &checkpoints, &thread_dataset);
+
+        thread_running.store(false, Ordering::Relaxed);
+        println!("L-BFGS complete!");
+    }
+
     pub fn update(&mut self, rl: &RaylibHandle){
         let current_epoch = self.training_state.epoch.load(Ordering::Relaxed);
         
@@ -114,6 +496,13 @@ impl Canvas {
             self.model_visualisation = ModelVisualisation::new(model);
             self.last_update_epoch = current_epoch;
         }
+
+        {
+            let history = self.training_state.history.lock().unwrap();
+            if history.epochs.len() != self.loss_visualisation.train_losses.len() {
+                self.loss_visualisation.update(&history);
+            }
+        }
         
         if rl.is_key_pressed(KeyboardKey::KEY_SPACE) {
             let current_state = self.training_state.is_running.load(Ordering::Relaxed);
@@ -132,6 +521,7 @@ impl Canvas {
             let mut camera_mode = d.begin_mode2D(self.camera.as_camera2d());
             self.model_visualisation.draw(&mut camera_mode);
         }
+        self.loss_visualisation.draw(d);
     }
 }
 
@@ -146,30 +536,487 @@ impl Drop for Canvas {
     }
 }
 
-fn create_spam_classifier() -> (Model, Dataset) {
-    let mut dataset = Dataset::load_data("spambase/spambase.data", 0.8).unwrap();
+/// Checkpoint directory of the run with `seed` under `base`.
+/// Saves what a training loop did since `first_epoch` and writes the
+/// end-of-run outputs into the run directory: the model of the checkpoint
+/// with the lowest validation loss as `model.txt`, the history, the
+/// baseline comparison and the requested reports on that model.
+fn finish_run(progress: &Checkpoint, first_epoch: usize, checkpoints: &CheckpointManager, dataset: &Dataset) {
+    if progress.epoch == 0 {
+        return;
+    }
+    // Keep the progress made since the last periodic checkpoint
+    if progress.epoch > first_epoch
+        && !checkpoints.is_due(progress.epoch)
+        && let Err(error) = checkpoints.save(progress)
+    {
+        println!("Failed to save checkpoint: {}", error);
+    }
+    let best = checkpoints.best().unwrap_or_else(|error| {
+        println!("Ignoring the best checkpoint: {}", error);
+        None
+    });
+    let model = match &best {
+        Some(best) if best.epoch != progress.epoch => {
+            println!("Using the best checkpoint, epoch {} (validation loss {:.4})", best.epoch, best.loss());
+            &best.model
+        }
+        _ => &progress.model,
+    };
+
+    let model_path = checkpoints.directory.join("model.txt");
+    if let Err(error) = persistence::save_model(model, progress.seed, &model_path) {
+        println!("Failed to save {}: {}", model_path.display(), error);
+    }
+    export_history(&progress.history, &checkpoints.directory);
+    compare_baselines(model, dataset, progress.seed);
+    print_reports(model, dataset, progress.seed, &checkpoints.directory);
+}
+
+fn run_directory(base: &str, seed: RunSeed) -> PathBuf {
+    Path::new(base).join(format!("seed-{}", seed))
+}
+
+/// Seed of the run under `base` that checkpointed most recently without
+/// reaching `max_epochs`, so that restarting the app picks it back up.
+fn unfinished_run(base: &str, max_epochs: usize) -> Option<RunSeed> {
+    let entries = fs::read_dir(base).ok()?;
+    entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name();
+            let seed = RunSeed(name.to_str()?.strip_prefix("seed-")?.parse().ok()?);
+            let (epoch, path) = CheckpointManager::new(entry.path()).list().ok()?.pop()?;
+            let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?;
+            (epoch < max_epochs).then_some((modified, seed))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, seed)| seed)
+}
+
+/// Writes `history.csv` and `history.json` next to the run's checkpoints.
+fn export_history(history: &TrainingHistory, directory: &Path) {
+    let csv_path = directory.join("history.csv");
+    let json_path = directory.join("history.json");
+    if let Err(error) = history.write_csv(&csv_path).and_then(|_| history.write_json(&json_path)) {
+        println!("Failed to export training history: {}", error);
+    }
+    if !history.batches.is_empty()
+        && let Err(error) = history.write_batches_csv(directory.join("batches.csv"))
+    {
+        println!("Failed to export batch history: {}", error);
+    }
+}
+
+/// Fits the classic baselines on the training split and prints them next
+/// to the network on the test split.
+fn compare_baselines(model: &Model, dataset: &Dataset, seed: RunSeed) {
+    let train_data = &dataset.train_data;
+    let input_width = train_data.first().map_or(0, |(input, _)| input.len());
+    let mut logistic = LogisticRegression::new(input_width, 1, &mut seed.rng("baseline", 0));
+    logistic.fit(train_data, 20, LEARNING_RATE, 1);
+    let gaussian = GaussianNaiveBayes::fit(train_data);
+    let bernoulli = BernoulliNaiveBayes::fit(train_data, 0.0, 1.0);
+    let knn = KNearestNeighbours::fit(train_data, 5);
+
+    let rows = baselines::compare(
+        &[
+            ("mlp", model),
+            ("logistic_regression", &logistic),
+            ("gaussian_naive_bayes", &gaussian),
+            ("bernoulli_naive_bayes", &bernoulli),
+            ("k_nearest_neighbours", &knn),
+        ],
+        &dataset.test_data,
+    );
+    baselines::print_comparison(&rows);
+}
+
+/// Prints the reports listed in `REPORTS` for the trained `model`. Files
+/// they write go to `directory`, next to the run's checkpoints.
+fn print_reports(model: &Model, dataset: &Dataset, seed: RunSeed, directory: &Path) {
+    let Ok(reports) = std::env::var(REPORTS_VAR) else {
+        return;
+    };
+    for report in reports.split(',').map(str::trim).filter(|report| !report.is_empty()) {
+        match report {
+            "pruning" => print_pruning_report(model, dataset),
+            "quantization" => print_quantization_report(model, dataset),
+            "search" => run_search(dataset, seed, directory),
+            "cross_validation" => print_cross_validation_report(model, dataset, seed, directory),
+            "ensemble" => print_ensemble_report(model, dataset, seed),
+            "attribution" => print_attribution_report(model, dataset, seed),
+            "regression" => print_regression_report(seed),
+            "transfer" => print_transfer_report(dataset, seed, directory),
+            "anomaly" => print_anomaly_report(dataset, seed),
+            other => println!("Unknown report: {}", other),
+        }
+    }
+}
+
+/// Test accuracy as more and more weights are pruned, each level briefly
+/// fine-tuned with its masks fixed.
+fn print_pruning_report(model: &Model, dataset: &Dataset) {
+    let (train_data, _) = dataset.validation_split(VALIDATION_RATIO);
+    let fine_tune = FineTune { data: train_data, learning_rate: LEARNING_RATE, epochs: 2 };
+    let tradeoff = |prune: fn(&mut Model, f64)| {
+        pruning::sparsity_tradeoff(model, prune, &PRUNING_LEVELS, &dataset.test_data, Some(&fine_tune))
+    };
+    println!("Global magnitude pruning:");
+    pruning::print_tradeoff(&tradeoff(pruning::prune_global));
+    println!("Per-layer magnitude pruning:");
+    pruning::print_tradeoff(&tradeoff(pruning::prune_per_layer));
+}
+
+/// How far int8 inference drifts from the float model on the test split.
+fn print_quantization_report(model: &Model, dataset: &Dataset) {
+    for granularity in [ScaleGranularity::PerLayer, ScaleGranularity::PerChannel] {
+        let quantized = QuantizedModel::calibrate(model, &dataset.train_data, CALIBRATION_SAMPLES, granularity);
+        println!("Int8 quantization, {:?} scales ({} bytes):", granularity, quantized.weight_bytes());
+        quantization::drift_report(model, &quantized, &dataset.test_data).print();
+    }
+}
+
+/// Hyperparameter search around the spam classifier's architecture,
+/// trained on the training part of the split and ranked on the validation
+/// part. Writes `search_results.csv` and `search_best.txt`.
+fn run_search(dataset: &Dataset, seed: RunSeed, directory: &Path) {
+    let space = SearchSpace {
+        learning_rates: vec![0.1, 0.03, 0.01, 0.003],
+        hidden_layers: vec![vec![16], vec![32, 16], vec![64, 32]],
+        activations: vec![Activation::ReLU, Activation::Sigmoid],
+        batch_sizes: vec![1, 16],
+        weight_decays: vec![0.0, 1e-4],
+    };
+    let (train_data, validation_data) = dataset.validation_split(VALIDATION_RATIO);
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let search = match Search::new(space, train_data, validation_data, seed) {
+        Ok(search) => search.with_threads(threads).with_ranking(search_ranking()),
+        Err(error) => {
+            println!("Search not started: {}", error);
+            return;
+        }
+    };
+
+    let results = search.run(&search_strategy());
+    let Some(best) = results.first() else {
+        return;
+    };
+    println!(
+        "Best of {} trials: {:?} after {} epochs, validation loss {:.4}, accuracy {:.4}",
+        results.len(),
+        best.config,
+        best.epochs,
+        best.validation_loss,
+        best.validation_accuracy
+    );
+    let written = search::write_results(&results, directory.join("search_results.csv"))
+        .and_then(|_| search::write_best_config(best, directory.join("search_best.txt")));
+    if let Err(error) = written {
+        println!("Failed to write search results: {}", error);
+    }
+}
+
+/// Cross-validates the architecture and settings of `model` on the
+/// training split, each fold starting from freshly initialized weights.
+/// Writes the out-of-fold predictions to `out_of_fold.csv`.
+fn print_cross_validation_report(model: &Model, dataset: &Dataset, seed: RunSeed, directory: &Path) {
+    let cross_validation = CrossValidation {
+        folds: CROSS_VALIDATION_FOLDS,
+        stratified: true,
+        epochs: CROSS_VALIDATION_EPOCHS,
+        learning_rate: LEARNING_RATE,
+        batch_size: 1,
+        seed,
+    };
+    // Stream index 0 initialized the trained model itself
+    let model_factory = |fold: usize| fresh_copy(model, seed.rng("init", fold as u64 + 1));
+    let report = cross_validation.run(&dataset.train_data, model_factory);
+
+    println!("{}-fold cross-validation:", CROSS_VALIDATION_FOLDS);
+    report.print();
+    if let Some(accuracy) = report.metric("accuracy") {
+        let folds: Vec<String> = accuracy.values.iter().map(|value| format!("{:.4}", value)).collect();
+        println!("Fold accuracies: {}", folds.join(", "));
+        let test_accuracy = model.evaluate(&dataset.test_data).accuracy;
+        println!(
+            "Trained model test accuracy: {:.4} ({:+.4} against the cross-validated mean)",
+            test_accuracy,
+            test_accuracy - accuracy.mean
+        );
+    }
+    if let Err(error) = report.write_out_of_fold(directory.join("out_of_fold.csv")) {
+        println!("Failed to write out-of-fold predictions: {}", error);
+    }
+}
+
+/// Bagged copies of `model`'s architecture, trained on the training part
+/// of the split, with the stacking layer fitted on the validation part.
+/// Compares the ways of combining them on the test split.
+fn print_ensemble_report(model: &Model, dataset: &Dataset, seed: RunSeed) {
+    let (train_data, validation_data) = dataset.validation_split(VALIDATION_RATIO);
+    let members = (0..ENSEMBLE_MEMBERS).map(|member| fresh_copy(model, seed.rng("ensemble", member as u64))).collect();
+    let mut ensemble = Ensemble::new(members, Combination::Average);
+    let mut rng = seed.rng("bagging", 0);
+    ensemble.train_bagging(train_data, ENSEMBLE_EPOCHS, LEARNING_RATE, 1, &mut rng);
+    ensemble.fit_stacker(validation_data, ENSEMBLE_EPOCHS, LEARNING_RATE, &mut rng);
+
+    let test_data = &dataset.test_data;
+    println!("Bagged ensemble of {} models:", ENSEMBLE_MEMBERS);
+    for combination in [Combination::Average, Combination::MajorityVote, Combination::Stacking] {
+        ensemble.combination = combination;
+        println!("{:>14}: accuracy {:.4}", format!("{:?}", combination), ensemble.evaluate(test_data).accuracy);
+    }
+    let disagreement = ensemble.disagreement_all(test_data);
+    let disputed = disagreement.iter().filter(|sample| sample.vote > 0.0).count();
+    let output_std = disagreement.iter().map(|sample| sample.output_std).sum::<f64>() / disagreement.len().max(1) as f64;
+    println!(
+        "Members disagree on {} of {} test emails, mean output std {:.4}",
+        disputed,
+        test_data.len(),
+        output_std
+    );
+}
+
+/// Which spambase features drive the predictions: permutation importance
+/// over the test split, then gradient × input and integrated gradients
+/// for the first spam email in it.
+fn print_attribution_report(model: &Model, dataset: &Dataset, seed: RunSeed) {
+    let names = dataset::load_feature_names("spambase/spambase.names").unwrap_or_else(|error| {
+        println!("Feature names unavailable: {}", error);
+        Vec::new()
+    });
+    let test_data = &dataset.test_data;
+
+    println!("Permutation importance (accuracy drop):");
+    let importance = attribution::permutation_importance(model, test_data, 3, &mut seed.rng("permutation", 0));
+    attribution::print_ranking(&attribution::rank(&importance, &names), TOP_FEATURES);
+
+    let Some((email, _)) = test_data.iter().find(|(_, target)| target[0] == 1.0) else {
+        return;
+    };
+    println!("Gradient x input for a spam email:");
+    let gradients = attribution::gradient_times_input(model, email, 0);
+    attribution::print_ranking(&attribution::rank(&gradients, &names), TOP_FEATURES);
+    println!("Integrated gradients for the same email:");
+    let integrated = attribution::integrated_gradients(model, email, &vec![0.0; email.len()], 0, 50);
+    attribution::print_ranking(&attribution::rank(&integrated, &names), TOP_FEATURES);
+}
+
+/// Fits the capital-letter run statistics of each email from its word and
+/// character frequencies, to check the training code on continuous,
+/// multi-output targets. The run lengths span several orders of magnitude,
+/// so the targets are `ln(1 + x)`.
+fn print_regression_report(seed: RunSeed) {
+    // The spam label is the last column, so it comes with the targets
+    let mut dataset = match Dataset::load_csv("spambase/spambase.data", REGRESSION_TARGETS + 1, 0.8, &mut seed.rng("split", 0)) {
+        Ok(dataset) => dataset,
+        Err(error) => {
+            println!("Regression data unavailable: {}", error);
+            return;
+        }
+    };
+    for (_, target) in dataset.train_data.iter_mut().chain(&mut dataset.test_data) {
+        target.truncate(REGRESSION_TARGETS);
+        target.iter_mut().for_each(|value| *value = value.ln_1p());
+    }
     dataset.normalize();
 
-    let hidden1 = create_layer(57, 32, Activation::ReLU);
-    let hidden2 = create_layer(32, 16, Activation::ReLU);
-    let output = create_layer(16, 1, Activation::Sigmoid);
+    let mut rng = seed.rng("regression", 0);
+    let layers = vec![
+        Layer::random(dataset.num_features(), 16, Activation::ReLU, &mut rng),
+        Layer::random(16, REGRESSION_TARGETS, Activation::Linear, &mut rng),
+    ];
+    let mut model = match Model::new(dataset.num_features(), layers) {
+        Ok(model) => model.with_loss(Loss::Huber { delta: 1.0 }),
+        Err(error) => {
+            println!("Regression model not built: {}", error);
+            return;
+        }
+    };
+    let mut context = TrainingContext::new();
+    for _ in 0..REGRESSION_EPOCHS {
+        model.train_epoch(&mut context, &dataset.train_data, LEARNING_RATE);
+    }
 
-    let model = Model::new(vec![hidden1, hidden2, output]);
+    let names = dataset::load_feature_names("spambase/spambase.names").unwrap_or_default();
+    let outputs = model.predict_all(&dataset.test_data);
+    let targets = metrics::targets(&dataset.test_data);
+    for (index, output) in metrics::regression_metrics_per_output(&outputs, &targets).iter().enumerate() {
+        let name = names.get(dataset.num_features() + index).map_or("target", String::as_str);
+        println!("{}: RMSE {:.4}, MAE {:.4}, R² {:.4}", name, output.rmse, output.mae, output.r2);
+    }
+}
 
-    (model, dataset)
+/// Reloads the run's `model.txt` as a pretrained feature extractor and
+/// compares ways of fine-tuning it, each for a few epochs: a new output
+/// layer on the frozen hidden layers, a deeper head in place of the output
+/// layer with only the first layer frozen, and a re-initialized output
+/// layer with layer-wise learning-rate decay on the rest.
+fn print_transfer_report(dataset: &Dataset, seed: RunSeed, directory: &Path) {
+    let saved = match persistence::load_model(directory.join("model.txt")) {
+        Ok(saved) => saved,
+        Err(error) => {
+            println!("Pretrained model unavailable: {}", error);
+            return;
+        }
+    };
+    let pretrained = with_training_settings(saved.model, dataset);
+    if let Some((input, _)) = dataset.test_data.first()
+        && let Err(error) = pretrained.try_forward(input)
+    {
+        println!("Pretrained model does not fit the data: {}", error);
+        return;
+    }
+
+    let variants = match transfer_variants(&pretrained, &mut seed.rng("transfer", 0)) {
+        Ok(variants) => variants,
+        Err(error) => {
+            println!("Model surgery failed: {}", error);
+            return;
+        }
+    };
+
+    let (train_data, _) = dataset.validation_split(VALIDATION_RATIO);
+    println!("Pretrained: test accuracy {:.4}", pretrained.evaluate(&dataset.test_data).accuracy);
+    for (name, mut model) in variants {
+        let mut context = TrainingContext::new();
+        for _ in 0..TRANSFER_EPOCHS {
+            model.train_epoch(&mut context, train_data, LEARNING_RATE);
+        }
+        println!("{:>12}: test accuracy {:.4}", name, model.evaluate(&dataset.test_data).accuracy);
+    }
+}
+
+/// The fine-tuning setups compared by `print_transfer_report`.
+fn transfer_variants(pretrained: &Model, rng: &mut impl Rng) -> Result<Vec<(&'static str, Model)>, ModelError> {
+    let last = pretrained.layers.len().saturating_sub(1);
+
+    let mut new_head = pretrained.clone();
+    new_head.replace_head(last, &[(1, Activation::Sigmoid)], rng)?;
+    new_head.freeze_all_but_last(1);
+
+    let mut deeper_head = pretrained.clone();
+    deeper_head.truncate(last)?;
+    deeper_head.append_dense(&[(8, Activation::ReLU)], rng)?;
+    deeper_head.append_layer(Layer::random(deeper_head.output_width(), 1, Activation::Sigmoid, rng))?;
+    deeper_head.freeze(0);
+
+    // Every layer trains, the pretrained ones at progressively smaller rates
+    let mut layerwise = pretrained.clone();
+    layerwise.reinitialize(last, rng)?;
+    for layer in 0..layerwise.layers.len() {
+        layerwise.unfreeze(layer);
+    }
+    let layerwise = layerwise.with_parameter_groups(ParameterGroup::layerwise_decay(
+        pretrained.layers.len(),
+        LAYERWISE_DECAY,
+        pretrained.weight_decay,
+    ));
+
+    Ok(vec![("new head", new_head), ("deeper head", deeper_head), ("layer-wise", layerwise)])
 }
-fn create_layer(num_inputs: usize, num_neurons: usize, activation: Activation) -> Layer {
-    let mut rng = rand::rng();
-    let mut perceptrons = Vec::new();
 
-    for _ in 0..num_neurons {
-        let weights: Vec<f64> = (0..num_inputs)
-            .map(|_| rng.random_range(-0.5..0.5))
+/// Trains an autoencoder on ham only and flags test emails it reconstructs
+/// worse than most ham as anomalies, to see how much spam stands out
+/// without ever seeing a spam label.
+fn print_anomaly_report(dataset: &Dataset, seed: RunSeed) {
+    let (train_data, validation_data) = dataset.validation_split(VALIDATION_RATIO);
+    let is_ham = |(_, target): &&(Vec<f64>, Vec<f64>)| target[0] == 0.0;
+    let ham: Vec<_> = train_data.iter().filter(is_ham).cloned().collect();
+    let validation_ham: Vec<_> = validation_data.iter().filter(is_ham).cloned().collect();
+
+    let input_width = dataset.num_features();
+    let mut autoencoder =
+        Autoencoder::new(input_width, &[16, 4], Activation::ReLU, Activation::Sigmoid, &mut seed.rng("autoencoder", 0));
+    for _ in 0..AUTOENCODER_EPOCHS {
+        autoencoder.train_epoch(&ham, LEARNING_RATE);
+    }
+
+    // Held-out ham sets the threshold, so it is not biased by the training fit
+    let threshold = autoencoder::anomaly_threshold(&autoencoder.reconstruction_errors(&validation_ham), ANOMALY_QUANTILE);
+    let flagged_rate = |spam: f64| {
+        let emails: Vec<_> = dataset.test_data.iter().filter(|(_, target)| target[0] == spam).cloned().collect();
+        let flagged = autoencoder.reconstruction_errors(&emails).iter().filter(|error| **error > threshold).count();
+        flagged as f64 / emails.len().max(1) as f64
+    };
+    println!(
+        "Autoencoder anomalies (error > {:.5}): {:.1}% of test spam, {:.1}% of test ham",
+        threshold,
+        100.0 * flagged_rate(1.0),
+        100.0 * flagged_rate(0.0)
+    );
+
+    let mean_embedding = |spam: f64| {
+        let embeddings: Vec<Vec<f64>> =
+            dataset.test_data.iter().filter(|(_, target)| target[0] == spam).map(|(input, _)| autoencoder.embed(input)).collect();
+        let mean: Vec<String> = (0..embeddings.first().map_or(0, Vec::len))
+            .map(|unit| embeddings.iter().map(|embedding| embedding[unit]).sum::<f64>() / embeddings.len() as f64)
+            .map(|value| format!("{:.3}", value))
             .collect();
-        let bias = rng.random_range(-0.1..0.1);
-        
-        perceptrons.push(Perceptron::new(weights, bias));
+        mean.join(", ")
+    };
+    println!("Mean bottleneck embedding: ham [{}], spam [{}]", mean_embedding(0.0), mean_embedding(1.0));
+}
+
+/// Copy of `model` with the same settings and freshly initialized weights.
+fn fresh_copy(model: &Model, mut rng: impl Rng) -> Model {
+    let mut fresh = model.clone();
+    for layer in &mut fresh.layers {
+        layer.reinitialize(&mut rng);
     }
+    fresh
+}
+
+fn search_strategy() -> Strategy {
+    match std::env::var(SEARCH_STRATEGY_VAR).as_deref().map(str::trim) {
+        Ok("grid") => Strategy::Grid { epochs: SEARCH_MAX_EPOCHS / 3 },
+        Ok("random") => Strategy::Random { trials: 20, epochs: SEARCH_MAX_EPOCHS / 3 },
+        Ok("halving") => Strategy::SuccessiveHalving { trials: 27, min_epochs: 1, reduction_factor: 3 },
+        _ => Strategy::Hyperband { max_epochs: SEARCH_MAX_EPOCHS, reduction_factor: 3 },
+    }
+}
+
+fn evolution_selection() -> Selection {
+    match std::env::var(SELECTION_VAR).as_deref().map(str::trim) {
+        Ok("elite") => Selection::Elite { count: POPULATION_SIZE / 5 },
+        _ => Selection::Tournament { size: 3 },
+    }
+}
+
+fn search_ranking() -> Ranking {
+    match std::env::var(SEARCH_RANKING_VAR).as_deref().map(str::trim) {
+        Ok("accuracy") => Ranking::ValidationAccuracy,
+        _ => Ranking::ValidationLoss,
+    }
+}
 
-    Layer::new(perceptrons, activation)
-}
\ No newline at end of file
+fn gradient_clipping() -> GradientClipping {
+    match std::env::var(CLIPPING_VAR).as_deref().map(str::trim) {
+        Ok("value") => GradientClipping::by_value(MAX_GRADIENT_VALUE),
+        Ok("none") => GradientClipping::none(),
+        _ => GradientClipping::by_norm(MAX_GRADIENT_NORM),
+    }
+}
+
+fn create_spam_classifier(seed: RunSeed) -> Result<(Model, Dataset), Box<dyn Error>> {
+    let mut dataset = Dataset::load_data("spambase/spambase.data", 0.8, &mut seed.rng("split", 0))?;
+    dataset.normalize();
+
+    let mut rng = seed.rng("init", 0);
+    let hidden1 = Layer::random(57, 32, Activation::ReLU, &mut rng);
+    let hidden2 = Layer::random(32, 16, Activation::ReLU, &mut rng);
+    let output = Layer::random(16, 1, Activation::Sigmoid, &mut rng);
+
+    let model = with_training_settings(Model::new(57, vec![hidden1, hidden2, output])?, &dataset);
+    Ok((model, dataset))
+}
+
+/// Class weights, clipping and numeric checks of the spam classifier, which
+/// `model.txt` does not store.
+fn with_training_settings(model: Model, dataset: &Dataset) -> Model {
+    // About 40% of the emails are spam; weight the classes so that both
+    // count equally in the loss
+    let class_weighting = ClassWeighting::balanced(dataset.balanced_class_weights());
+    model.with_class_weighting(class_weighting).with_gradient_clipping(gradient_clipping()).with_numeric_checks()
+}
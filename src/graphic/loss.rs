@@ -1,3 +1,63 @@
-struct LossVisualisation {
-    pub loss: f64,
-}
\ No newline at end of file
+use raylib::prelude::*;
+use crate::ml::history::TrainingHistory;
+
+/// Line plot of the per-epoch training and validation loss, drawn in screen
+/// space.
+pub struct LossVisualisation {
+    pub train_losses: Vec<f64>,
+    pub validation_losses: Vec<f64>,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl LossVisualisation {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self { train_losses: Vec::new(), validation_losses: Vec::new(), x, y, width, height }
+    }
+
+    pub fn update(&mut self, history: &TrainingHistory) {
+        self.train_losses = history.train_losses();
+        self.validation_losses = history.validation_losses();
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle) {
+        d.draw_rectangle(self.x, self.y, self.width, self.height, Color::WHITE);
+        d.draw_rectangle_lines(self.x, self.y, self.width, self.height, Color::GRAY);
+
+        let max_loss = self
+            .train_losses
+            .iter()
+            .chain(&self.validation_losses)
+            .copied()
+            .filter(|loss| loss.is_finite())
+            .fold(0.0, f64::max);
+        let epochs = self.train_losses.len().max(self.validation_losses.len());
+        if epochs < 2 || max_loss <= 0.0 {
+            d.draw_text("loss: waiting for data", self.x + 8, self.y + 8, 14, Color::DARKGRAY);
+            return;
+        }
+
+        self.draw_series(d, &self.train_losses, epochs, max_loss, Color::BLUE);
+        self.draw_series(d, &self.validation_losses, epochs, max_loss, Color::ORANGE);
+
+        let mut legend = format!("train {:.4}", self.train_losses.last().copied().unwrap_or(0.0));
+        if let Some(validation) = self.validation_losses.last() {
+            legend.push_str(&format!("  validation {:.4}", validation));
+        }
+        d.draw_text(&legend, self.x + 8, self.y + 8, 14, Color::DARKGRAY);
+    }
+
+    fn draw_series(&self, d: &mut RaylibDrawHandle, losses: &[f64], epochs: usize, max_loss: f64, color: Color) {
+        let point = |(epoch, loss): (usize, &f64)| {
+            let x = self.x as f32 + self.width as f32 * epoch as f32 / (epochs - 1) as f32;
+            let y = self.y as f32 + self.height as f32 * (1.0 - (loss.min(max_loss) / max_loss) as f32);
+            Vector2::new(x, y)
+        };
+        let points: Vec<Vector2> = losses.iter().enumerate().map(point).collect();
+        for pair in points.windows(2) {
+            d.draw_line_v(pair[0], pair[1], color);
+        }
+    }
+}
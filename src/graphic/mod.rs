@@ -4,4 +4,5 @@ pub mod nodes;
 pub mod connection;
 pub mod model_visualisation;
 pub mod layout_config;
-pub mod camera;
\ No newline at end of file
+pub mod camera;
+pub mod loss;
\ No newline at end of file
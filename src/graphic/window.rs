@@ -20,7 +20,13 @@ impl Window{
             .title(&self.title)
             .build();
 
-        let mut canvas = Canvas::new(self.width, self.height, Color::BLACK);
+        let mut canvas = match Canvas::new(self.width, self.height, Color::BLACK) {
+            Ok(canvas) => canvas,
+            Err(error) => {
+                println!("Failed to start: {}", error);
+                return;
+            }
+        };
 
         while !rl.window_should_close() {
             canvas.update(&rl);
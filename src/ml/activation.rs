@@ -1,7 +1,11 @@
-#[derive(Clone, Copy)]
+use crate::ml::simd;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Activation {
     ReLU,
     Sigmoid,
+    /// Identity, for regression outputs.
+    Linear,
 }
 
 impl Activation {
@@ -9,6 +13,16 @@ impl Activation {
         match self {
             Activation::ReLU => x.max(0.0),
             Activation::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+            Activation::Linear => x,
+        }
+    }
+
+    /// `activate` applied in place to every value, using the SIMD kernels.
+    pub fn activate_all(&self, values: &mut [f64]) {
+        match self {
+            Activation::ReLU => simd::relu(values),
+            Activation::Sigmoid => simd::sigmoid(values),
+            Activation::Linear => {}
         }
     }
 
@@ -25,6 +39,25 @@ impl Activation {
                 let s = self.activate(x);
                 s * (1.0 - s)
             }
+            Activation::Linear => 1.0,
+        }
+    }
+
+    /// Lowercase name used in saved model files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Activation::ReLU => "relu",
+            Activation::Sigmoid => "sigmoid",
+            Activation::Linear => "linear",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "relu" => Some(Activation::ReLU),
+            "sigmoid" => Some(Activation::Sigmoid),
+            "linear" => Some(Activation::Linear),
+            _ => None,
         }
     }
 }
@@ -75,4 +108,12 @@ mod tests {
         assert!(sigmoid.derivative(0.0) > sigmoid.derivative(2.0));
         assert!(sigmoid.derivative(0.0) > sigmoid.derivative(-2.0));
     }
+
+    #[test]
+    fn test_linear() {
+        let linear = Activation::Linear;
+
+        assert_eq!(linear.activate(-3.5), -3.5);
+        assert_eq!(linear.derivative(-3.5), 1.0);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,165 @@
+use rand::Rng;
+use rand::prelude::SliceRandom;
+use crate::ml::model::Model;
+use crate::ml::predictor::Predictor;
+
+/// Score of one input feature.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attribution {
+    pub feature: usize,
+    pub name: String,
+    pub score: f64,
+}
+
+/// Pairs scores with feature names, largest magnitude first. Features
+/// without a name are labelled by index.
+pub fn rank(scores: &[f64], names: &[String]) -> Vec<Attribution> {
+    let mut ranked: Vec<Attribution> = scores
+        .iter()
+        .enumerate()
+        .map(|(feature, &score)| Attribution {
+            feature,
+            name: names.get(feature).cloned().unwrap_or_else(|| format!("feature_{}", feature)),
+            score,
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.score.abs().total_cmp(&a.score.abs()));
+    ranked
+}
+
+pub fn print_ranking(ranking: &[Attribution], top: usize) {
+    for (position, attribution) in ranking.iter().take(top).enumerate() {
+        println!("{:>3}. {:<28} {:+.5}", position + 1, attribution.name, attribution.score);
+    }
+}
+
+/// Drop in accuracy on `data` when each feature column is shuffled,
+/// averaged over `repeats` shuffles. Works for any `Predictor`.
+pub fn permutation_importance(
+    predictor: &impl Predictor,
+    data: &[(Vec<f64>, Vec<f64>)],
+    repeats: usize,
+    rng: &mut impl Rng,
+) -> Vec<f64> {
+    let baseline = predictor.evaluate(data).accuracy;
+    let num_features = data.first().map_or(0, |(input, _)| input.len());
+    let mut permuted = data.to_vec();
+
+    (0..num_features)
+        .map(|feature| {
+            let mut total_drop = 0.0;
+            for _ in 0..repeats.max(1) {
+                let mut column: Vec<f64> = data.iter().map(|(input, _)| input[feature]).collect();
+                column.shuffle(rng);
+                for ((input, _), value) in permuted.iter_mut().zip(column) {
+                    input[feature] = value;
+                }
+                total_drop += baseline - predictor.evaluate(&permuted).accuracy;
+            }
+            for ((input, _), (original, _)) in permuted.iter_mut().zip(data) {
+                input[feature] = original[feature];
+            }
+            total_drop / repeats.max(1) as f64
+        })
+        .collect()
+}
+
+/// Gradient of `output_index` with respect to each feature of `input`,
+/// multiplied by the input (gradient × input).
+pub fn gradient_times_input(model: &Model, input: &[f64], output_index: usize) -> Vec<f64> {
+    model
+        .input_gradients(input, output_index)
+        .iter()
+        .zip(input)
+        .map(|(gradient, x)| gradient * x)
+        .collect()
+}
+
+/// Integrated gradients: the input gradients averaged along the straight
+/// path from `baseline` to `input` (midpoint rule, `steps` points), scaled
+/// by `input - baseline`. The scores sum approximately to
+/// `f(input) - f(baseline)`.
+pub fn integrated_gradients(
+    model: &Model,
+    input: &[f64],
+    baseline: &[f64],
+    output_index: usize,
+    steps: usize,
+) -> Vec<f64> {
+    let steps = steps.max(1);
+    let mut total = vec![0.0; input.len()];
+
+    for step in 0..steps {
+        let alpha = (step as f64 + 0.5) / steps as f64;
+        let point: Vec<f64> = baseline.iter().zip(input).map(|(b, x)| b + alpha * (x - b)).collect();
+        for (sum, gradient) in total.iter_mut().zip(model.input_gradients(&point, output_index)) {
+            *sum += gradient;
+        }
+    }
+
+    total
+        .iter()
+        .zip(input.iter().zip(baseline))
+        .map(|(sum, (x, b))| sum / steps as f64 * (x - b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::activation::Activation;
+    use crate::ml::layer::Layer;
+    use crate::ml::perceptron::Perceptron;
+
+    // Only the first feature matters
+    fn model() -> Model {
+        let hidden = Layer::new(vec![
+            Perceptron::new(vec![4.0, 0.0], -2.0),
+            Perceptron::new(vec![-1.0, 0.0], 1.0),
+        ], Activation::Sigmoid);
+        let output = Layer::new(vec![
+            Perceptron::new(vec![3.0, -2.0], 0.0),
+        ], Activation::Sigmoid);
+        Model::new(2, vec![hidden, output]).unwrap()
+    }
+
+    #[test]
+    fn test_rank_by_magnitude() {
+        let names = vec!["a".to_string(), "b".to_string()];
+        let ranking = rank(&[0.1, -0.5, 0.3], &names);
+
+        let order: Vec<&str> = ranking.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(order, vec!["b", "feature_2", "a"]);
+    }
+
+    #[test]
+    fn test_permutation_importance() {
+        let model = model();
+        // Labels are the model's own predictions, so the baseline accuracy is 1
+        let data: Vec<(Vec<f64>, Vec<f64>)> = (0..20)
+            .map(|i| {
+                let input = vec![i as f64 / 20.0, 0.3];
+                let target = if model.forward(&input)[0] > 0.5 { 1.0 } else { 0.0 };
+                (input, vec![target])
+            })
+            .collect();
+
+        let importance = permutation_importance(&model, &data, 5, &mut rand::rng());
+        assert!(importance[0] > 0.0);
+        assert_eq!(importance[1], 0.0);
+    }
+
+    #[test]
+    fn test_integrated_gradients_completeness() {
+        let model = model();
+        let input = [0.9, 0.4];
+        let baseline = [0.0, 0.0];
+
+        let scores = integrated_gradients(&model, &input, &baseline, 0, 200);
+        let difference = model.forward(&input)[0] - model.forward(&baseline)[0];
+
+        assert!((scores.iter().sum::<f64>() - difference).abs() < 1e-4);
+        assert_eq!(scores[1], 0.0);
+        assert_eq!(gradient_times_input(&model, &input, 0)[1], 0.0);
+    }
+}
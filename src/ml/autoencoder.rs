@@ -0,0 +1,143 @@
+use rand::Rng;
+use crate::ml::activation::Activation;
+use crate::ml::error::ModelError;
+use crate::ml::layer::Layer;
+use crate::ml::loss::Loss;
+use crate::ml::model::Model;
+use crate::ml::training::TrainingContext;
+
+/// Encoder and decoder stacks trained to reproduce their input. The output
+/// of the last encoder layer is the bottleneck embedding.
+///
+/// Methods take the usual `(input, target)` rows and ignore the targets, so
+/// labelled and unlabelled datasets (`Dataset::load_csv` with no target
+/// columns) both work.
+#[derive(Clone)]
+pub struct Autoencoder {
+    pub model: Model,
+    encoder_depth: usize,
+}
+
+impl Autoencoder {
+    /// Symmetric autoencoder: `encoder_widths` (ending with the bottleneck
+    /// width) and the same widths mirrored back to `input_width`, with
+    /// `output_activation` on the reconstruction.
+    pub fn new(
+        input_width: usize,
+        encoder_widths: &[usize],
+        activation: Activation,
+        output_activation: Activation,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let mut encoder = Vec::new();
+        let mut width = input_width;
+        for &next in encoder_widths {
+            encoder.push(Layer::random(width, next, activation, rng));
+            width = next;
+        }
+
+        let mut decoder = Vec::new();
+        let decoder_widths = encoder_widths.iter().rev().skip(1).chain(std::iter::once(&input_width));
+        for (index, &next) in decoder_widths.enumerate() {
+            let is_output = index + 1 == encoder_widths.len();
+            decoder.push(Layer::random(width, next, if is_output { output_activation } else { activation }, rng));
+            width = next;
+        }
+
+        Self::from_stacks(input_width, encoder, decoder).expect("mirrored widths always connect")
+    }
+
+    /// Builds an autoencoder from explicit stacks. The decoder must end with
+    /// `input_width` outputs.
+    pub fn from_stacks(input_width: usize, encoder: Vec<Layer>, decoder: Vec<Layer>) -> Result<Self, ModelError> {
+        let encoder_depth = encoder.len();
+        let model = Model::new(input_width, encoder.into_iter().chain(decoder).collect())?
+            .with_loss(Loss::MeanSquaredError);
+        if model.output_width() != input_width {
+            return Err(ModelError::TargetSize { expected: model.output_width(), found: input_width });
+        }
+        Ok(Self { model, encoder_depth })
+    }
+
+    pub fn encoder(&self) -> &[Layer] {
+        &self.model.layers[..self.encoder_depth]
+    }
+
+    /// One pass over `data` with each input as its own target. Returns the
+    /// mean loss.
+    pub fn train_epoch(&mut self, data: &[(Vec<f64>, Vec<f64>)], learning_rate: f64) -> f64 {
+        let mut context = TrainingContext::new();
+        let total: f64 = data.iter().map(|(input, _)| self.model.train(&mut context, input, input, learning_rate)).sum();
+        total / data.len().max(1) as f64
+    }
+
+    /// Bottleneck activations for `input`.
+    pub fn embed(&self, input: &[f64]) -> Vec<f64> {
+        self.encoder().iter().fold(input.to_vec(), |current, layer| layer.forward(&current))
+    }
+
+    pub fn reconstruct(&self, input: &[f64]) -> Vec<f64> {
+        self.model.forward(input)
+    }
+
+    /// Mean squared difference between `input` and its reconstruction.
+    pub fn reconstruction_error(&self, input: &[f64]) -> f64 {
+        Loss::MeanSquaredError.compute(&self.reconstruct(input), input)
+    }
+
+    /// `reconstruction_error` of every row of `data`, usable as an anomaly
+    /// score.
+    pub fn reconstruction_errors(&self, data: &[(Vec<f64>, Vec<f64>)]) -> Vec<f64> {
+        data.iter().map(|(input, _)| self.reconstruction_error(input)).collect()
+    }
+}
+
+/// The `quantile` (0..=1) of `errors`, e.g. 0.99 to flag the worst 1% of
+/// samples as anomalies.
+pub fn anomaly_threshold(errors: &[f64], quantile: f64) -> f64 {
+    let mut sorted = errors.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let index = ((sorted.len() as f64 - 1.0) * quantile.clamp(0.0, 1.0)).round() as usize;
+    sorted.get(index).copied().unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::seed::RunSeed;
+
+    #[test]
+    fn test_symmetric_shape() {
+        let mut rng = RunSeed(1).rng("init", 0);
+        let autoencoder = Autoencoder::new(6, &[4, 2], Activation::ReLU, Activation::Sigmoid, &mut rng);
+
+        let widths: Vec<usize> = autoencoder.model.layers.iter().map(|layer| layer.perceptrons.len()).collect();
+        assert_eq!(widths, vec![4, 2, 4, 6]);
+        assert_eq!(autoencoder.model.layers[3].activation, Activation::Sigmoid);
+        assert_eq!(autoencoder.embed(&[0.5; 6]).len(), 2);
+    }
+
+    #[test]
+    fn test_off_manifold_points_score_higher() {
+        // Points on the line (t, 2t, -t) compress to one value
+        let data: Vec<(Vec<f64>, Vec<f64>)> =
+            (0..20).map(|i| (vec![i as f64 / 20.0, i as f64 / 10.0, -(i as f64) / 20.0], Vec::new())).collect();
+        let mut rng = RunSeed(2).rng("init", 0);
+        let mut autoencoder = Autoencoder::new(3, &[1], Activation::Linear, Activation::Linear, &mut rng);
+
+        for _ in 0..500 {
+            autoencoder.train_epoch(&data, 0.05);
+        }
+
+        let errors = autoencoder.reconstruction_errors(&data);
+        let threshold = anomaly_threshold(&errors, 1.0);
+        assert!(threshold < 1e-3);
+        assert!(autoencoder.reconstruction_error(&[0.5, -0.5, 0.5]) > 10.0 * threshold);
+    }
+
+    #[test]
+    fn test_anomaly_threshold() {
+        assert_eq!(anomaly_threshold(&[3.0, 1.0, 2.0, 4.0, 5.0], 0.5), 3.0);
+        assert_eq!(anomaly_threshold(&[], 0.9), 0.0);
+    }
+}
@@ -0,0 +1,320 @@
+use rand::Rng;
+use crate::ml::activation::Activation;
+use crate::ml::layer::Layer;
+use crate::ml::loss::Loss;
+use crate::ml::metrics;
+use crate::ml::model::Model;
+use crate::ml::predictor::{Evaluation, Predictor};
+use crate::ml::training::TrainingContext;
+
+/// Fraction of the largest feature variance added to every variance, as in
+/// scikit-learn, so constant features do not divide by zero.
+const VAR_SMOOTHING: f64 = 1e-9;
+
+/// A single sigmoid layer trained with binary cross-entropy through the
+/// regular `Model` training code.
+pub struct LogisticRegression {
+    pub model: Model,
+}
+
+impl LogisticRegression {
+    pub fn new(input_width: usize, output_width: usize, rng: &mut impl Rng) -> Self {
+        let layer = Layer::random(input_width, output_width, Activation::Sigmoid, rng);
+        let model = Model::new(input_width, vec![layer])
+            .expect("a single layer always matches the input")
+            .with_loss(Loss::BinaryCrossEntropy);
+        Self { model }
+    }
+
+    pub fn fit(&mut self, data: &[(Vec<f64>, Vec<f64>)], epochs: usize, learning_rate: f64, batch_size: usize) {
+        let mut context = TrainingContext::new();
+        for _ in 0..epochs {
+            self.model.train_epoch_batched(&mut context, data, learning_rate, batch_size);
+        }
+    }
+}
+
+impl Predictor for LogisticRegression {
+    fn predict(&self, input: &[f64]) -> Vec<f64> {
+        self.model.predict(input)
+    }
+}
+
+/// Naive Bayes with a normal distribution per class and feature.
+pub struct GaussianNaiveBayes {
+    log_priors: Vec<f64>,
+    means: Vec<Vec<f64>>,
+    variances: Vec<Vec<f64>>,
+    output_width: usize,
+}
+
+impl GaussianNaiveBayes {
+    pub fn fit(data: &[(Vec<f64>, Vec<f64>)]) -> Self {
+        let classes = Classes::of(data);
+        let num_features = data.first().map_or(0, |(input, _)| input.len());
+
+        let mut means = vec![vec![0.0; num_features]; classes.count()];
+        for ((input, _), &class) in data.iter().zip(&classes.labels) {
+            for (mean, x) in means[class].iter_mut().zip(input) {
+                *mean += x / classes.sizes[class].max(1) as f64;
+            }
+        }
+        let mut variances = vec![vec![0.0; num_features]; classes.count()];
+        for ((input, _), &class) in data.iter().zip(&classes.labels) {
+            for ((variance, mean), x) in variances[class].iter_mut().zip(&means[class]).zip(input) {
+                *variance += (x - mean).powi(2) / classes.sizes[class].max(1) as f64;
+            }
+        }
+
+        let largest = variances.iter().flatten().copied().fold(0.0, f64::max);
+        let epsilon = (VAR_SMOOTHING * largest).max(1e-12);
+        for variance in variances.iter_mut().flatten() {
+            *variance += epsilon;
+        }
+
+        Self { log_priors: classes.log_priors(), means, variances, output_width: classes.output_width }
+    }
+}
+
+impl Predictor for GaussianNaiveBayes {
+    fn predict(&self, input: &[f64]) -> Vec<f64> {
+        let scores = self.log_priors.iter().zip(self.means.iter().zip(&self.variances)).map(|(prior, (means, variances))| {
+            prior
+                + input
+                    .iter()
+                    .zip(means.iter().zip(variances))
+                    .map(|(x, (mean, variance))| {
+                        -0.5 * ((2.0 * std::f64::consts::PI * variance).ln() + (x - mean).powi(2) / variance)
+                    })
+                    .sum::<f64>()
+        });
+        class_output(scores.collect(), self.output_width)
+    }
+}
+
+/// Naive Bayes on binary features: a feature is present when it exceeds
+/// `threshold`, e.g. a word-frequency column above zero.
+pub struct BernoulliNaiveBayes {
+    pub threshold: f64,
+    log_priors: Vec<f64>,
+    /// ln P(present | class) and ln P(absent | class) per feature.
+    log_present: Vec<Vec<f64>>,
+    log_absent: Vec<Vec<f64>>,
+    output_width: usize,
+}
+
+impl BernoulliNaiveBayes {
+    /// Fits with Laplace smoothing `alpha` (1.0 is the usual choice).
+    pub fn fit(data: &[(Vec<f64>, Vec<f64>)], threshold: f64, alpha: f64) -> Self {
+        let classes = Classes::of(data);
+        let num_features = data.first().map_or(0, |(input, _)| input.len());
+
+        let mut counts = vec![vec![0.0; num_features]; classes.count()];
+        for ((input, _), &class) in data.iter().zip(&classes.labels) {
+            for (count, x) in counts[class].iter_mut().zip(input) {
+                if *x > threshold {
+                    *count += 1.0;
+                }
+            }
+        }
+
+        let probabilities: Vec<Vec<f64>> = counts
+            .iter()
+            .zip(&classes.sizes)
+            .map(|(counts, &size)| counts.iter().map(|count| (count + alpha) / (size as f64 + 2.0 * alpha)).collect())
+            .collect();
+
+        Self {
+            threshold,
+            log_priors: classes.log_priors(),
+            log_present: probabilities.iter().map(|p| p.iter().map(|p| p.ln()).collect()).collect(),
+            log_absent: probabilities.iter().map(|p| p.iter().map(|p| (1.0 - p).ln()).collect()).collect(),
+            output_width: classes.output_width,
+        }
+    }
+}
+
+impl Predictor for BernoulliNaiveBayes {
+    fn predict(&self, input: &[f64]) -> Vec<f64> {
+        let scores = self.log_priors.iter().zip(self.log_present.iter().zip(&self.log_absent)).map(|(prior, (present, absent))| {
+            prior
+                + input
+                    .iter()
+                    .zip(present.iter().zip(absent))
+                    .map(|(x, (present, absent))| if *x > self.threshold { present } else { absent })
+                    .sum::<f64>()
+        });
+        class_output(scores.collect(), self.output_width)
+    }
+}
+
+/// Averages the targets of the `k` training rows closest to the input
+/// (Euclidean distance).
+pub struct KNearestNeighbours {
+    pub k: usize,
+    data: Vec<(Vec<f64>, Vec<f64>)>,
+}
+
+impl KNearestNeighbours {
+    pub fn fit(data: &[(Vec<f64>, Vec<f64>)], k: usize) -> Self {
+        Self { k: k.max(1), data: data.to_vec() }
+    }
+}
+
+impl Predictor for KNearestNeighbours {
+    fn predict(&self, input: &[f64]) -> Vec<f64> {
+        let mut distances: Vec<(f64, &Vec<f64>)> = self
+            .data
+            .iter()
+            .map(|(features, target)| {
+                let distance = features.iter().zip(input).map(|(a, b)| (a - b).powi(2)).sum::<f64>();
+                (distance, target)
+            })
+            .collect();
+        let k = self.k.min(distances.len());
+        if k == 0 {
+            return Vec::new();
+        }
+        distances.select_nth_unstable_by(k - 1, |a, b| a.0.total_cmp(&b.0));
+
+        let mut output = vec![0.0; distances[0].1.len()];
+        for (_, target) in &distances[..k] {
+            for (sum, value) in output.iter_mut().zip(target.iter()) {
+                *sum += value / k as f64;
+            }
+        }
+        output
+    }
+}
+
+/// Class of every row and the class sizes. Single-output targets are
+/// treated as two classes, wider ones as one-hot.
+struct Classes {
+    labels: Vec<usize>,
+    sizes: Vec<usize>,
+    output_width: usize,
+}
+
+impl Classes {
+    fn of(data: &[(Vec<f64>, Vec<f64>)]) -> Self {
+        let output_width = data.first().map_or(1, |(_, target)| target.len());
+        let mut sizes = vec![0; output_width.max(2)];
+        let labels = data
+            .iter()
+            .map(|(_, target)| {
+                let class = metrics::predicted_class(target);
+                sizes[class] += 1;
+                class
+            })
+            .collect();
+        Self { labels, sizes, output_width }
+    }
+
+    fn count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    fn log_priors(&self) -> Vec<f64> {
+        let total = self.labels.len().max(1) as f64;
+        self.sizes.iter().map(|&size| (size.max(1) as f64 / total).ln()).collect()
+    }
+}
+
+/// Turns per-class log scores into probabilities, shaped like the targets:
+/// `[P(class 1)]` for a single output, otherwise one value per class.
+fn class_output(log_scores: Vec<f64>, output_width: usize) -> Vec<f64> {
+    let max = log_scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let exps: Vec<f64> = log_scores.iter().map(|score| (score - max).exp()).collect();
+    let total: f64 = exps.iter().sum();
+    let probabilities: Vec<f64> = exps.iter().map(|e| e / total).collect();
+    if output_width == 1 {
+        vec![probabilities[1]]
+    } else {
+        probabilities
+    }
+}
+
+/// One row of a model comparison.
+#[derive(Clone, Debug)]
+pub struct ComparisonRow {
+    pub name: String,
+    pub evaluation: Evaluation,
+}
+
+/// Evaluates every named model on `data`.
+pub fn compare(models: &[(&str, &dyn Predictor)], data: &[(Vec<f64>, Vec<f64>)]) -> Vec<ComparisonRow> {
+    models
+        .iter()
+        .map(|(name, model)| ComparisonRow { name: name.to_string(), evaluation: model.evaluate(data) })
+        .collect()
+}
+
+pub fn print_comparison(rows: &[ComparisonRow]) {
+    println!("{:<24} {:>9} {:>8} {:>8}", "model", "accuracy", "rmse", "mae");
+    for row in rows {
+        let evaluation = &row.evaluation;
+        println!(
+            "{:<24} {:>9.4} {:>8.4} {:>8.4}",
+            row.name, evaluation.accuracy, evaluation.regression.rmse, evaluation.regression.mae
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::seed::RunSeed;
+
+    // Class 1 when the first two features are large; the third is noise
+    fn data() -> Vec<(Vec<f64>, Vec<f64>)> {
+        (0..40)
+            .map(|i| {
+                let class = i % 2;
+                let x = class as f64 * 0.6 + (i % 5) as f64 * 0.08;
+                let y = if class == 1 { ((i % 3) + 1) as f64 * 0.3 } else { 0.0 };
+                (vec![x, y, (i % 7) as f64 / 7.0], vec![class as f64])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_baselines_separate_classes() {
+        let data = data();
+        let mut logistic = LogisticRegression::new(3, 1, &mut RunSeed(1).rng("baseline", 0));
+        logistic.fit(&data, 200, 0.5, 4);
+        let gaussian = GaussianNaiveBayes::fit(&data);
+        let bernoulli = BernoulliNaiveBayes::fit(&data, 0.0, 1.0);
+        let knn = KNearestNeighbours::fit(&data, 3);
+
+        let rows = compare(
+            &[("logistic", &logistic), ("gaussian_nb", &gaussian), ("bernoulli_nb", &bernoulli), ("knn", &knn)],
+            &data,
+        );
+        assert_eq!(rows.len(), 4);
+        for row in &rows {
+            assert!(row.evaluation.accuracy > 0.9, "{} accuracy {}", row.name, row.evaluation.accuracy);
+        }
+    }
+
+    #[test]
+    fn test_naive_bayes_one_hot_outputs() {
+        let data: Vec<(Vec<f64>, Vec<f64>)> = (0..30)
+            .map(|i| {
+                let class = i % 3;
+                (vec![class as f64 + (i % 4) as f64 * 0.1], (0..3).map(|c| if c == class { 1.0 } else { 0.0 }).collect())
+            })
+            .collect();
+
+        let output = GaussianNaiveBayes::fit(&data).predict(&[2.1]);
+        assert_eq!(output.len(), 3);
+        assert!((output.iter().sum::<f64>() - 1.0).abs() < 1e-10);
+        assert_eq!(metrics::predicted_class(&output), 2);
+    }
+
+    #[test]
+    fn test_knn_averages_neighbours() {
+        let data = vec![(vec![0.0], vec![0.0]), (vec![1.0], vec![1.0]), (vec![1.1], vec![1.0]), (vec![5.0], vec![0.0])];
+        let knn = KNearestNeighbours::fit(&data, 3);
+        assert!((knn.predict(&[0.9])[0] - 2.0 / 3.0).abs() < 1e-10);
+    }
+}
@@ -0,0 +1,478 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use crate::ml::clipping::GradientClipping;
+use crate::ml::error::LoadError;
+use crate::ml::history::{EpochRecord, TrainingHistory};
+use crate::ml::loss::{ClassWeighting, Loss};
+use crate::ml::model::Model;
+use crate::ml::persistence::{self, LineReader};
+use crate::ml::seed::RunSeed;
+use crate::ml::training::ParameterGroup;
+
+const HEADER: &str = "checkpoint v2";
+const BEST_FILE: &str = "best.ckpt";
+
+/// Everything needed to continue a run exactly where it stopped.
+///
+/// Training uses plain SGD, so the optimizer state is just the learning
+/// rate, and the schedule position is `epoch`. Every random stream is
+/// derived from `(seed, epoch)` (see `RunSeed::rng`), so the seed and the
+/// epoch also capture the RNG state. The model's training settings (loss,
+/// class weighting, weight decay, clipping, numeric checks and parameter
+/// groups) are saved with it.
+#[derive(Clone)]
+pub struct Checkpoint {
+    pub model: Model,
+    pub seed: RunSeed,
+    /// Number of completed epochs.
+    pub epoch: usize,
+    pub learning_rate: f64,
+    /// Per-epoch records of the run so far. Batch records are not saved.
+    pub history: TrainingHistory,
+}
+
+impl Checkpoint {
+    /// Loss of the last completed epoch, used to pick the best checkpoint:
+    /// the validation loss when it was measured, else the training loss.
+    pub fn loss(&self) -> f64 {
+        self.history.last().map_or(f64::INFINITY, |record| record.validation_loss.unwrap_or(record.train_loss))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Writes the run state followed by the model in the format of
+    /// `persistence::write_model`.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "{}", HEADER)?;
+        writeln!(writer, "epoch {}", self.epoch)?;
+        writeln!(writer, "learning_rate {}", self.learning_rate)?;
+        write_settings(writer, &self.model)?;
+        writeln!(writer, "history {}", self.history.epochs.len())?;
+        for record in &self.history.epochs {
+            let optional = |value: Option<f64>| value.map_or("none".to_string(), |v| v.to_string());
+            writeln!(
+                writer,
+                "{} {} {} {} {} {} {} {}",
+                record.epoch,
+                record.train_loss,
+                optional(record.validation_loss),
+                optional(record.train_accuracy),
+                optional(record.validation_accuracy),
+                record.learning_rate,
+                record.gradient_norm,
+                record.seconds,
+            )?;
+        }
+        persistence::write_model(writer, &self.model, self.seed)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        let name = path.display().to_string();
+        let file = File::open(path).map_err(|source| LoadError::Io { path: name.clone(), source })?;
+        Self::read(BufReader::new(file), &name)
+    }
+
+    pub fn read(reader: impl BufRead, path: &str) -> Result<Self, LoadError> {
+        let mut lines = LineReader::new(reader, path);
+
+        let header = lines.expect("header")?;
+        if header != HEADER {
+            return Err(lines.error(format!("expected {:?}, found {:?}", HEADER, header)));
+        }
+        let epoch = lines.field("epoch")?;
+        let learning_rate = lines.field("learning_rate")?;
+        let settings = read_settings(&mut lines)?;
+        let count: usize = lines.field("history")?;
+        let mut history = TrainingHistory::new();
+        for _ in 0..count {
+            let line = lines.expect("history record")?;
+            let values: Vec<&str> = line.split_whitespace().collect();
+            if values.len() != 8 {
+                return Err(lines.error(format!("expected 8 history values, found {}", values.len())));
+            }
+            let optional = |value: &str| match value {
+                "none" => Ok(None),
+                value => lines.parse(value).map(Some),
+            };
+            history.record_epoch(EpochRecord {
+                epoch: lines.parse(values[0])?,
+                train_loss: lines.parse(values[1])?,
+                validation_loss: optional(values[2])?,
+                train_accuracy: optional(values[3])?,
+                validation_accuracy: optional(values[4])?,
+                learning_rate: lines.parse(values[5])?,
+                gradient_norm: lines.parse(values[6])?,
+                seconds: lines.parse(values[7])?,
+            });
+        }
+
+        let mut saved = persistence::read_model_from(&mut lines)?;
+        settings.apply(&mut saved.model);
+        Ok(Self { model: saved.model, seed: saved.seed, epoch, learning_rate, history })
+    }
+}
+
+/// The training settings of a `Model`, which the model section of a
+/// checkpoint does not store.
+struct Settings {
+    loss: Loss,
+    class_weighting: ClassWeighting,
+    weight_decay: f64,
+    clipping: GradientClipping,
+    check_numerics: bool,
+    parameter_groups: Vec<ParameterGroup>,
+}
+
+impl Settings {
+    fn apply(self, model: &mut Model) {
+        model.loss = self.loss;
+        model.class_weighting = self.class_weighting;
+        model.weight_decay = self.weight_decay;
+        model.clipping = self.clipping;
+        model.check_numerics = self.check_numerics;
+        model.parameter_groups = self.parameter_groups;
+    }
+}
+
+fn write_settings(writer: &mut impl Write, model: &Model) -> io::Result<()> {
+    let optional = |value: Option<f64>| value.map_or("none".to_string(), |v| v.to_string());
+    let list = |values: &[f64]| values.iter().map(f64::to_string).collect::<Vec<_>>().join(" ");
+
+    let loss = match model.loss {
+        Loss::SumSquaredError => "sum_squared_error".to_string(),
+        Loss::MeanSquaredError => "mean_squared_error".to_string(),
+        Loss::MeanAbsoluteError => "mean_absolute_error".to_string(),
+        Loss::Huber { delta } => format!("huber {}", delta),
+        Loss::LogCosh => "log_cosh".to_string(),
+        Loss::Quantile { quantile } => format!("quantile {}", quantile),
+        Loss::BinaryCrossEntropy => "binary_cross_entropy".to_string(),
+        Loss::Focal { gamma, alpha } => format!("focal {} {}", gamma, alpha),
+    };
+    writeln!(writer, "loss {}", loss)?;
+
+    let weighting = &model.class_weighting;
+    writeln!(writer, "class_weights {}", weighting.class_weights.as_deref().map_or("none".to_string(), list))?;
+    match &weighting.cost_matrix {
+        Some(rows) => {
+            writeln!(writer, "cost_matrix {}", rows.len())?;
+            for row in rows {
+                writeln!(writer, "{}", list(row))?;
+            }
+        }
+        None => writeln!(writer, "cost_matrix none")?,
+    }
+    writeln!(writer, "label_smoothing {}", weighting.label_smoothing)?;
+
+    writeln!(writer, "weight_decay {}", model.weight_decay)?;
+    writeln!(writer, "clipping {} {}", optional(model.clipping.max_value), optional(model.clipping.max_norm))?;
+    writeln!(writer, "check_numerics {}", model.check_numerics)?;
+    writeln!(writer, "parameter_groups {}", model.parameter_groups.len())?;
+    for group in &model.parameter_groups {
+        writeln!(
+            writer,
+            "{} {} {} {}",
+            group.layers.start,
+            group.layers.end,
+            group.learning_rate_scale,
+            group.weight_decay,
+        )?;
+    }
+    Ok(())
+}
+
+fn read_settings<R: BufRead>(lines: &mut LineReader<R>) -> Result<Settings, LoadError> {
+    fn numbers<R: BufRead>(lines: &LineReader<R>, values: &str) -> Result<Vec<f64>, LoadError> {
+        values.split_whitespace().map(|value| lines.parse(value)).collect()
+    }
+    fn optional<R: BufRead>(lines: &LineReader<R>, value: &str) -> Result<Option<f64>, LoadError> {
+        match value {
+            "none" => Ok(None),
+            value => lines.parse(value).map(Some),
+        }
+    }
+
+    let loss: String = lines.field("loss")?;
+    let parts: Vec<&str> = loss.split_whitespace().collect();
+    let loss = match parts.as_slice() {
+        ["sum_squared_error"] => Loss::SumSquaredError,
+        ["mean_squared_error"] => Loss::MeanSquaredError,
+        ["mean_absolute_error"] => Loss::MeanAbsoluteError,
+        ["huber", delta] => Loss::Huber { delta: lines.parse(delta)? },
+        ["log_cosh"] => Loss::LogCosh,
+        ["quantile", quantile] => Loss::Quantile { quantile: lines.parse(quantile)? },
+        ["binary_cross_entropy"] => Loss::BinaryCrossEntropy,
+        ["focal", gamma, alpha] => Loss::Focal { gamma: lines.parse(gamma)?, alpha: lines.parse(alpha)? },
+        _ => return Err(lines.error(format!("unknown loss {:?}", loss))),
+    };
+
+    let class_weights: String = lines.field("class_weights")?;
+    let class_weights = match class_weights.as_str() {
+        "none" => None,
+        values => Some(numbers(lines, values)?),
+    };
+    let cost_matrix: String = lines.field("cost_matrix")?;
+    let cost_matrix = match cost_matrix.as_str() {
+        "none" => None,
+        rows => {
+            let rows: usize = lines.parse(rows)?;
+            let mut matrix = Vec::with_capacity(rows);
+            for _ in 0..rows {
+                let row = lines.expect("cost matrix row")?;
+                matrix.push(numbers(lines, &row)?);
+            }
+            Some(matrix)
+        }
+    };
+    let label_smoothing = lines.field("label_smoothing")?;
+
+    let weight_decay = lines.field("weight_decay")?;
+    let clipping: String = lines.field("clipping")?;
+    let clipping = match clipping.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [max_value, max_norm] => {
+            GradientClipping { max_value: optional(lines, max_value)?, max_norm: optional(lines, max_norm)? }
+        }
+        _ => return Err(lines.error(format!("expected `clipping <max_value> <max_norm>`, found {:?}", clipping))),
+    };
+    let check_numerics = lines.field("check_numerics")?;
+
+    let count: usize = lines.field("parameter_groups")?;
+    let mut parameter_groups = Vec::with_capacity(count);
+    for _ in 0..count {
+        let line = lines.expect("parameter group")?;
+        let values: Vec<&str> = line.split_whitespace().collect();
+        if values.len() != 4 {
+            return Err(lines.error(format!("expected 4 parameter group values, found {}", values.len())));
+        }
+        parameter_groups.push(ParameterGroup::new(
+            lines.parse(values[0])?..lines.parse(values[1])?,
+            lines.parse(values[2])?,
+            lines.parse(values[3])?,
+        ));
+    }
+
+    Ok(Settings {
+        loss,
+        class_weighting: ClassWeighting { class_weights, cost_matrix, label_smoothing },
+        weight_decay,
+        clipping,
+        check_numerics,
+        parameter_groups,
+    })
+}
+
+/// Writes checkpoints to `directory` as `epoch-000012.ckpt` and applies a
+/// retention policy: only the newest `keep_last` epoch files are kept, and
+/// the checkpoint with the lowest loss is also kept as `best.ckpt`.
+pub struct CheckpointManager {
+    pub directory: PathBuf,
+    /// Save every `interval` epochs.
+    pub interval: usize,
+    pub keep_last: usize,
+}
+
+impl CheckpointManager {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: directory.into(), interval: 1, keep_last: 3 }
+    }
+
+    pub fn with_interval(mut self, interval: usize) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_keep_last(mut self, keep_last: usize) -> Self {
+        self.keep_last = keep_last;
+        self
+    }
+
+    /// Whether a checkpoint is due after `epoch` completed epochs.
+    pub fn is_due(&self, epoch: usize) -> bool {
+        epoch > 0 && epoch.is_multiple_of(self.interval.max(1))
+    }
+
+    /// Saves `checkpoint`, updates `best.ckpt` and removes epoch files
+    /// outside the retention policy. Returns the path written.
+    pub fn save(&self, checkpoint: &Checkpoint) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.directory)?;
+        let path = self.directory.join(format!("epoch-{:06}.ckpt", checkpoint.epoch));
+        checkpoint.save(&path)?;
+
+        let best_loss = self.best().ok().flatten().map_or(f64::INFINITY, |best| best.loss());
+        if checkpoint.loss() < best_loss {
+            fs::copy(&path, self.directory.join(BEST_FILE))?;
+        }
+
+        let epochs = self.list()?;
+        for (_, old) in epochs.iter().rev().skip(self.keep_last.max(1)) {
+            fs::remove_file(old)?;
+        }
+        Ok(path)
+    }
+
+    /// Epoch checkpoint files in `directory`, oldest first.
+    pub fn list(&self) -> io::Result<Vec<(usize, PathBuf)>> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+
+        let mut checkpoints = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let epoch = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("epoch-")?.strip_suffix(".ckpt")?.parse().ok());
+            if let Some(epoch) = epoch {
+                checkpoints.push((epoch, path));
+            }
+        }
+        checkpoints.sort();
+        Ok(checkpoints)
+    }
+
+    /// The most recent checkpoint, if any.
+    pub fn latest(&self) -> Result<Option<Checkpoint>, LoadError> {
+        let checkpoints = self.list().map_err(|source| LoadError::Io {
+            path: self.directory.display().to_string(),
+            source,
+        })?;
+        checkpoints.last().map(|(_, path)| Checkpoint::load(path)).transpose()
+    }
+
+    pub fn best(&self) -> Result<Option<Checkpoint>, LoadError> {
+        let path = self.directory.join(BEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Checkpoint::load(&path).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::seq::SliceRandom;
+    use crate::ml::activation::Activation;
+    use crate::ml::layer::Layer;
+    use crate::ml::training::TrainingContext;
+
+    fn data() -> Vec<(Vec<f64>, Vec<f64>)> {
+        (0..12).map(|i| (vec![i as f64 / 12.0], vec![if i >= 6 { 1.0 } else { 0.0 }])).collect()
+    }
+
+    fn start(seed: RunSeed) -> Checkpoint {
+        let layer = Layer::random(1, 1, Activation::Sigmoid, &mut seed.rng("init", 0));
+        let model = Model::new(1, vec![layer]).unwrap();
+        Checkpoint { model, seed, epoch: 0, learning_rate: 0.5, history: TrainingHistory::new() }
+    }
+
+    fn record(epoch: usize, train_loss: f64) -> EpochRecord {
+        EpochRecord {
+            epoch,
+            train_loss,
+            validation_loss: None,
+            train_accuracy: None,
+            validation_accuracy: Some(0.5),
+            learning_rate: 0.5,
+            gradient_norm: 1.0,
+            seconds: 0.25,
+        }
+    }
+
+    fn train(checkpoint: &mut Checkpoint, epochs: usize) {
+        let mut context = TrainingContext::new();
+        for _ in 0..epochs {
+            let mut data = data();
+            data.shuffle(&mut checkpoint.seed.rng("shuffle", checkpoint.epoch as u64));
+            let loss = checkpoint.model.train_epoch(&mut context, &data, checkpoint.learning_rate);
+            checkpoint.epoch += 1;
+            checkpoint.history.record_epoch(record(checkpoint.epoch, loss));
+        }
+    }
+
+    #[test]
+    fn test_resume_matches_uninterrupted_run() {
+        let mut straight = start(RunSeed(11));
+        train(&mut straight, 6);
+
+        let mut first = start(RunSeed(11));
+        train(&mut first, 3);
+        let mut buffer = Vec::new();
+        first.write(&mut buffer).unwrap();
+        let mut resumed = Checkpoint::read(buffer.as_slice(), "checkpoint").unwrap();
+        train(&mut resumed, 3);
+
+        assert_eq!(resumed.epoch, 6);
+        assert_eq!(resumed.history.epochs, straight.history.epochs);
+        let weights = |c: &Checkpoint| c.model.layers[0].perceptrons[0].weights[0].to_bits();
+        assert_eq!(weights(&resumed), weights(&straight));
+    }
+
+    #[test]
+    fn test_round_trip_keeps_training_settings() {
+        let mut checkpoint = start(RunSeed(5));
+        checkpoint.model = checkpoint
+            .model
+            .with_loss(Loss::Focal { gamma: 2.0, alpha: 0.25 })
+            .with_class_weighting(ClassWeighting {
+                class_weights: Some(vec![1.0, 3.0]),
+                cost_matrix: Some(vec![vec![0.0, 1.0], vec![5.0, 0.0]]),
+                label_smoothing: 0.1,
+            })
+            .with_weight_decay(1e-4)
+            .with_gradient_clipping(GradientClipping::by_norm(1.0))
+            .with_numeric_checks()
+            .with_parameter_groups(vec![ParameterGroup::new(0..1, 0.5, 1e-3)]);
+        checkpoint.model.freeze(0);
+
+        let mut buffer = Vec::new();
+        checkpoint.write(&mut buffer).unwrap();
+        let model = Checkpoint::read(buffer.as_slice(), "checkpoint").unwrap().model;
+
+        assert_eq!(model.loss, checkpoint.model.loss);
+        assert_eq!(model.class_weighting, checkpoint.model.class_weighting);
+        assert_eq!(model.weight_decay, 1e-4);
+        assert_eq!((model.clipping.max_value, model.clipping.max_norm), (None, Some(1.0)));
+        assert!(model.check_numerics);
+        assert_eq!(model.parameter_groups, checkpoint.model.parameter_groups);
+        assert!(!model.layers[0].trainable);
+    }
+
+    #[test]
+    fn test_retention_keeps_last_and_best() {
+        let directory = std::env::temp_dir().join("checkpoint_retention_test");
+        let _ = fs::remove_dir_all(&directory);
+        let manager = CheckpointManager::new(&directory).with_keep_last(2);
+
+        let mut checkpoint = start(RunSeed(3));
+        for (epoch, loss) in [0.5, 0.1, 0.3, 0.4].into_iter().enumerate() {
+            checkpoint.epoch = epoch + 1;
+            checkpoint.history.record_epoch(record(epoch + 1, loss));
+            manager.save(&checkpoint).unwrap();
+        }
+
+        let epochs: Vec<usize> = manager.list().unwrap().into_iter().map(|(epoch, _)| epoch).collect();
+        assert_eq!(epochs, vec![3, 4]);
+        assert_eq!(manager.latest().unwrap().unwrap().epoch, 4);
+        assert_eq!(manager.best().unwrap().unwrap().epoch, 2);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_loss_prefers_validation() {
+        let mut checkpoint = start(RunSeed(4));
+        assert_eq!(checkpoint.loss(), f64::INFINITY);
+        checkpoint.history.record_epoch(record(1, 0.2));
+        assert_eq!(checkpoint.loss(), 0.2);
+        checkpoint.history.record_epoch(EpochRecord { validation_loss: Some(0.7), ..record(2, 0.1) });
+        assert_eq!(checkpoint.loss(), 0.7);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::ml::layer::LayerCache;
+
+/// Limits applied to the parameter gradients of a step before the update.
+/// Value clipping runs first, then the global norm is rescaled if needed.
+#[derive(Clone, Copy, Default)]
+pub struct GradientClipping {
+    pub max_value: Option<f64>,
+    pub max_norm: Option<f64>,
+}
+
+impl GradientClipping {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn by_value(max_value: f64) -> Self {
+        Self { max_value: Some(max_value), max_norm: None }
+    }
+
+    pub fn by_norm(max_norm: f64) -> Self {
+        Self { max_value: None, max_norm: Some(max_norm) }
+    }
+
+    /// Clips the gradients stored in `caches` and returns the global norm
+    /// measured before any clipping.
+    pub fn apply(&self, caches: &mut [LayerCache]) -> f64 {
+        let pre_clip_norm = global_norm(caches);
+
+        if let Some(max_value) = self.max_value {
+            for cache in caches.iter_mut() {
+                cache.clamp_gradients(max_value);
+            }
+        }
+
+        if let Some(max_norm) = self.max_norm {
+            let norm = global_norm(caches);
+            if norm > max_norm {
+                let factor = max_norm / norm;
+                for cache in caches.iter_mut() {
+                    cache.scale_gradients(factor);
+                }
+            }
+        }
+
+        pre_clip_norm
+    }
+}
+
+/// L2 norm of every weight and bias gradient across all layers.
+pub fn global_norm(caches: &[LayerCache]) -> f64 {
+    caches.iter().map(|cache| cache.gradient_squared_norm()).sum::<f64>().sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::layer::Layer;
+    use crate::ml::perceptron::Perceptron;
+    use crate::ml::activation::Activation;
+
+    // Weight gradients [3.0, 4.0] and bias gradient 1.0 -> norm sqrt(26)
+    fn cache_with_gradients() -> LayerCache {
+        let layer = Layer::new(vec![Perceptron::new(vec![1.0, 1.0], 0.0)], Activation::ReLU);
+        let mut cache = LayerCache::default();
+        layer.forward_cached(&[3.0, 4.0], &mut cache);
+        layer.compute_gradients(&mut cache, &[1.0]);
+        cache
+    }
+
+    #[test]
+    fn test_no_clipping_reports_norm() {
+        let mut caches = vec![cache_with_gradients()];
+        let norm = GradientClipping::none().apply(&mut caches);
+
+        assert!((norm - 26f64.sqrt()).abs() < 1e-10);
+        assert!((global_norm(&caches) - norm).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_clip_by_norm() {
+        let mut caches = vec![cache_with_gradients(), cache_with_gradients()];
+        let norm = GradientClipping::by_norm(1.0).apply(&mut caches);
+
+        assert!((norm - 52f64.sqrt()).abs() < 1e-10);
+        assert!((global_norm(&caches) - 1.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_clip_by_value() {
+        let mut caches = vec![cache_with_gradients()];
+        GradientClipping::by_value(0.5).apply(&mut caches);
+
+        // Every gradient is clamped to 0.5
+        assert!((global_norm(&caches) - 0.75f64.sqrt()).abs() < 1e-10);
+    }
+}
\ No newline at end of file
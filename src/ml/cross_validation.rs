@@ -0,0 +1,222 @@
+use std::io;
+use std::path::Path;
+use rand::Rng;
+use rand::prelude::SliceRandom;
+use crate::ml::metrics;
+use crate::ml::model::Model;
+use crate::ml::seed::RunSeed;
+use crate::ml::training::TrainingContext;
+
+/// Splits the indices of `data` into `k` folds of near-equal size. With
+/// `stratified`, each class (see `metrics::predicted_class`) is spread
+/// evenly over the folds.
+pub fn k_folds(data: &[(Vec<f64>, Vec<f64>)], k: usize, stratified: bool, rng: &mut impl Rng) -> Vec<Vec<usize>> {
+    let k = k.max(1);
+    let mut groups: Vec<Vec<usize>> = if stratified {
+        let mut by_class: Vec<Vec<usize>> = Vec::new();
+        for (index, (_, target)) in data.iter().enumerate() {
+            let class = metrics::predicted_class(target);
+            if by_class.len() <= class {
+                by_class.resize(class + 1, Vec::new());
+            }
+            by_class[class].push(index);
+        }
+        by_class
+    } else {
+        vec![(0..data.len()).collect()]
+    };
+
+    let mut folds = vec![Vec::new(); k];
+    let mut next_fold = 0;
+    for group in &mut groups {
+        group.shuffle(rng);
+        for &index in group.iter() {
+            folds[next_fold].push(index);
+            next_fold = (next_fold + 1) % k;
+        }
+    }
+    folds
+}
+
+pub struct CrossValidation {
+    pub folds: usize,
+    pub stratified: bool,
+    pub epochs: usize,
+    pub learning_rate: f64,
+    pub batch_size: usize,
+    /// Drives the fold assignment.
+    pub seed: RunSeed,
+}
+
+#[derive(Clone, Debug)]
+pub struct MetricSummary {
+    pub name: &'static str,
+    /// One value per fold.
+    pub values: Vec<f64>,
+    pub mean: f64,
+    pub std: f64,
+}
+
+pub struct CrossValidationReport {
+    pub metrics: Vec<MetricSummary>,
+    /// Prediction for every sample from the model that did not train on it,
+    /// in the order of the input data.
+    pub out_of_fold: Vec<Vec<f64>>,
+    /// Fold in which each sample was held out.
+    pub fold_of_sample: Vec<usize>,
+}
+
+impl CrossValidationReport {
+    pub fn metric(&self, name: &str) -> Option<&MetricSummary> {
+        self.metrics.iter().find(|metric| metric.name == name)
+    }
+
+    pub fn print(&self) {
+        for metric in &self.metrics {
+            println!("{:>10}: {:.4} ± {:.4}", metric.name, metric.mean, metric.std);
+        }
+    }
+
+    /// Writes one CSV row per sample: its index, the fold that held it out
+    /// and the out-of-fold outputs.
+    pub fn write_out_of_fold(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = csv::Writer::from_path(path)?;
+        let outputs = self.out_of_fold.first().map_or(0, Vec::len);
+        let columns = (0..outputs).map(|i| format!("output_{}", i));
+        writer.write_record(["sample".to_string(), "fold".to_string()].into_iter().chain(columns))?;
+        for (sample, (output, fold)) in self.out_of_fold.iter().zip(&self.fold_of_sample).enumerate() {
+            let values = [sample.to_string(), fold.to_string()].into_iter().chain(output.iter().map(f64::to_string));
+            writer.write_record(values)?;
+        }
+        writer.flush()
+    }
+}
+
+impl CrossValidation {
+    /// Trains a fresh model from `model_factory` (called with the fold
+    /// index) on each set of `folds - 1` folds and scores it on the
+    /// remaining one.
+    pub fn run(&self, data: &[(Vec<f64>, Vec<f64>)], model_factory: impl Fn(usize) -> Model) -> CrossValidationReport {
+        let folds = k_folds(data, self.folds, self.stratified, &mut self.seed.rng("folds", 0));
+        let mut out_of_fold = vec![Vec::new(); data.len()];
+        let mut fold_of_sample = vec![0; data.len()];
+        let mut scores: Vec<(&'static str, Vec<f64>)> =
+            ["loss", "accuracy", "rmse", "mae", "r2"].iter().map(|&name| (name, Vec::new())).collect();
+
+        let mut context = TrainingContext::new();
+        for (fold, held_out) in folds.iter().enumerate() {
+            let train: Vec<(Vec<f64>, Vec<f64>)> = folds
+                .iter()
+                .enumerate()
+                .filter(|(other, _)| *other != fold)
+                .flat_map(|(_, indices)| indices.iter().map(|&i| data[i].clone()))
+                .collect();
+            let validation: Vec<(Vec<f64>, Vec<f64>)> = held_out.iter().map(|&i| data[i].clone()).collect();
+
+            let mut model = model_factory(fold);
+            for _ in 0..self.epochs {
+                model.train_epoch_batched(&mut context, &train, self.learning_rate, self.batch_size);
+            }
+
+            let outputs = model.forward_all(&validation);
+            let targets = metrics::targets(&validation);
+            let loss: f64 = outputs.iter().zip(&targets).map(|(o, t)| model.loss.compute(o, t)).sum::<f64>()
+                / targets.len().max(1) as f64;
+            let regression = metrics::regression_metrics(&outputs, &targets);
+            let fold_scores = [loss, metrics::accuracy(&outputs, &targets), regression.rmse, regression.mae, regression.r2];
+            for ((_, values), score) in scores.iter_mut().zip(fold_scores) {
+                values.push(score);
+            }
+
+            for (&index, output) in held_out.iter().zip(outputs) {
+                out_of_fold[index] = output;
+                fold_of_sample[index] = fold;
+            }
+        }
+
+        let metrics = scores
+            .into_iter()
+            .map(|(name, values)| {
+                let (mean, std) = mean_and_std(&values);
+                MetricSummary { name, values, mean, std }
+            })
+            .collect();
+        CrossValidationReport { metrics, out_of_fold, fold_of_sample }
+    }
+}
+
+/// Mean and population standard deviation.
+fn mean_and_std(values: &[f64]) -> (f64, f64) {
+    let count = values.len().max(1) as f64;
+    let mean = values.iter().sum::<f64>() / count;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::activation::Activation;
+    use crate::ml::layer::Layer;
+
+    fn data() -> Vec<(Vec<f64>, Vec<f64>)> {
+        (0..30)
+            .map(|i| {
+                let x = i as f64 / 30.0;
+                (vec![x], vec![if i % 3 == 0 { 1.0 } else { 0.0 }])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_k_folds_partition() {
+        let data = data();
+        let folds = k_folds(&data, 4, false, &mut rand::rng());
+
+        let mut all: Vec<usize> = folds.iter().flatten().copied().collect();
+        all.sort();
+        assert_eq!(all, (0..30).collect::<Vec<_>>());
+        assert!(folds.iter().all(|fold| fold.len() == 7 || fold.len() == 8));
+    }
+
+    #[test]
+    fn test_stratified_folds_keep_class_ratio() {
+        let data = data();
+        let folds = k_folds(&data, 5, true, &mut rand::rng());
+
+        // 10 positives over 5 folds -> exactly 2 each
+        for fold in &folds {
+            let positives = fold.iter().filter(|&&i| data[i].1[0] == 1.0).count();
+            assert_eq!(positives, 2);
+        }
+    }
+
+    #[test]
+    fn test_run_reports_every_fold() {
+        let data = data();
+        let cross_validation = CrossValidation { folds: 3, stratified: true, epochs: 2, learning_rate: 0.1, batch_size: 4, seed: RunSeed(5) };
+        let report = cross_validation.run(&data, |_| {
+            let layer = Layer::random(1, 1, Activation::Sigmoid, &mut rand::rng());
+            Model::new(1, vec![layer]).unwrap()
+        });
+
+        let accuracy = report.metric("accuracy").unwrap();
+        assert_eq!(accuracy.values.len(), 3);
+        assert!(accuracy.std >= 0.0);
+        assert!(report.out_of_fold.iter().all(|output| output.len() == 1));
+        assert!(report.fold_of_sample.iter().all(|&fold| fold < 3));
+
+        let path = std::env::temp_dir().join("cross_validation_out_of_fold.csv");
+        report.write_out_of_fold(&path).unwrap();
+        let table = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(table.lines().next(), Some("sample,fold,output_0"));
+        assert_eq!(table.lines().count(), 31);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_mean_and_std() {
+        let (mean, std) = mean_and_std(&[1.0, 3.0]);
+        assert_eq!((mean, std), (2.0, 1.0));
+    }
+}
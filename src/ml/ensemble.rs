@@ -0,0 +1,195 @@
+use rand::Rng;
+use crate::ml::activation::Activation;
+use crate::ml::layer::Layer;
+use crate::ml::metrics;
+use crate::ml::model::Model;
+use crate::ml::predictor::Predictor;
+use crate::ml::training::TrainingContext;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Combination {
+    /// Mean of the member outputs.
+    Average,
+    /// Share of members voting for each class (for a single output, the
+    /// share voting 1).
+    MajorityVote,
+    /// A sigmoid layer trained on the concatenated member outputs by
+    /// `fit_stacker`. Falls back to `Average` until it is fitted.
+    Stacking,
+}
+
+/// How much the members of an ensemble disagree on one sample.
+#[derive(Clone, Copy, Debug)]
+pub struct Disagreement {
+    /// Fraction of members whose class differs from the majority's.
+    pub vote: f64,
+    /// Standard deviation of the member outputs, averaged over outputs.
+    pub output_std: f64,
+}
+
+pub struct Ensemble {
+    pub members: Vec<Model>,
+    pub combination: Combination,
+    stacker: Option<Model>,
+}
+
+impl Ensemble {
+    pub fn new(members: Vec<Model>, combination: Combination) -> Self {
+        Self { members, combination, stacker: None }
+    }
+
+    /// Trains every member on its own bootstrap sample: `data.len()` rows
+    /// drawn with replacement.
+    pub fn train_bagging(
+        &mut self,
+        data: &[(Vec<f64>, Vec<f64>)],
+        epochs: usize,
+        learning_rate: f64,
+        batch_size: usize,
+        rng: &mut impl Rng,
+    ) {
+        let mut context = TrainingContext::new();
+        for member in &mut self.members {
+            let sample: Vec<(Vec<f64>, Vec<f64>)> =
+                (0..data.len()).map(|_| data[rng.random_range(0..data.len())].clone()).collect();
+            for _ in 0..epochs {
+                member.train_epoch_batched(&mut context, &sample, learning_rate, batch_size);
+            }
+        }
+    }
+
+    /// Fits the stacking layer on the members' outputs for `data`, which
+    /// should be held out from the members' own training data.
+    pub fn fit_stacker(&mut self, data: &[(Vec<f64>, Vec<f64>)], epochs: usize, learning_rate: f64, rng: &mut impl Rng) {
+        let stacked: Vec<(Vec<f64>, Vec<f64>)> =
+            data.iter().map(|(input, target)| (self.member_outputs(input).concat(), target.clone())).collect();
+        let input_width = stacked.first().map_or(0, |(input, _)| input.len());
+        let output_width = stacked.first().map_or(1, |(_, target)| target.len());
+
+        let layer = Layer::random(input_width, output_width, Activation::Sigmoid, rng);
+        let mut stacker = Model::new(input_width, vec![layer]).expect("stacking layer matches member outputs");
+        let mut context = TrainingContext::new();
+        for _ in 0..epochs {
+            stacker.train_epoch(&mut context, &stacked, learning_rate);
+        }
+        self.stacker = Some(stacker);
+    }
+
+    pub fn member_outputs(&self, input: &[f64]) -> Vec<Vec<f64>> {
+        self.members.iter().map(|member| member.forward(input)).collect()
+    }
+
+    pub fn disagreement(&self, input: &[f64]) -> Disagreement {
+        let outputs = self.member_outputs(input);
+        let votes = vote_shares(&outputs);
+        let majority = metrics::predicted_class(&votes);
+        let dissenting = outputs.iter().filter(|output| metrics::predicted_class(output) != majority).count();
+
+        let count = outputs.len().max(1) as f64;
+        let width = outputs.first().map_or(0, Vec::len);
+        let output_std = (0..width)
+            .map(|i| {
+                let mean = outputs.iter().map(|o| o[i]).sum::<f64>() / count;
+                (outputs.iter().map(|o| (o[i] - mean).powi(2)).sum::<f64>() / count).sqrt()
+            })
+            .sum::<f64>()
+            / width.max(1) as f64;
+
+        Disagreement { vote: dissenting as f64 / count, output_std }
+    }
+
+    pub fn disagreement_all(&self, data: &[(Vec<f64>, Vec<f64>)]) -> Vec<Disagreement> {
+        data.iter().map(|(input, _)| self.disagreement(input)).collect()
+    }
+}
+
+impl Predictor for Ensemble {
+    fn predict(&self, input: &[f64]) -> Vec<f64> {
+        let outputs = self.member_outputs(input);
+        match (self.combination, &self.stacker) {
+            (Combination::MajorityVote, _) => vote_shares(&outputs),
+            (Combination::Stacking, Some(stacker)) => stacker.forward(&outputs.concat()),
+            (Combination::Average, _) | (Combination::Stacking, None) => average(&outputs),
+        }
+    }
+}
+
+fn average(outputs: &[Vec<f64>]) -> Vec<f64> {
+    let width = outputs.first().map_or(0, Vec::len);
+    let count = outputs.len().max(1) as f64;
+    (0..width).map(|i| outputs.iter().map(|o| o[i]).sum::<f64>() / count).collect()
+}
+
+fn vote_shares(outputs: &[Vec<f64>]) -> Vec<f64> {
+    let width = outputs.first().map_or(0, Vec::len);
+    let count = outputs.len().max(1) as f64;
+    let mut shares = vec![0.0; width];
+    for output in outputs {
+        let class = metrics::predicted_class(output);
+        // A single output votes for class 1 or 0
+        let slot = if width == 1 { (class == 1).then_some(0) } else { Some(class) };
+        if let Some(slot) = slot {
+            shares[slot] += 1.0 / count;
+        }
+    }
+    shares
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::perceptron::Perceptron;
+    use crate::ml::seed::RunSeed;
+
+    fn constant_model(bias: f64) -> Model {
+        let layer = Layer::new(vec![Perceptron::new(vec![0.0], bias)], Activation::Sigmoid);
+        Model::new(1, vec![layer]).unwrap()
+    }
+
+    // Members output sigmoid(2) = 0.88, sigmoid(1) = 0.73 and sigmoid(-3) = 0.05
+    fn ensemble(combination: Combination) -> Ensemble {
+        Ensemble::new(vec![constant_model(2.0), constant_model(1.0), constant_model(-3.0)], combination)
+    }
+
+    #[test]
+    fn test_average_and_vote() {
+        let average = ensemble(Combination::Average).predict(&[0.0])[0];
+        let expected = (Activation::Sigmoid.activate(2.0) + Activation::Sigmoid.activate(1.0)
+            + Activation::Sigmoid.activate(-3.0)) / 3.0;
+        assert!((average - expected).abs() < 1e-10);
+
+        let vote = ensemble(Combination::MajorityVote).predict(&[0.0])[0];
+        assert!((vote - 2.0 / 3.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_disagreement() {
+        let disagreement = ensemble(Combination::Average).disagreement(&[0.0]);
+        assert!((disagreement.vote - 1.0 / 3.0).abs() < 1e-10);
+        assert!(disagreement.output_std > 0.3);
+
+        let unanimous = Ensemble::new(vec![constant_model(1.0), constant_model(1.0)], Combination::Average);
+        let disagreement = unanimous.disagreement(&[0.0]);
+        assert_eq!(disagreement.vote, 0.0);
+        assert!(disagreement.output_std.abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_bagging_and_stacking() {
+        let data: Vec<(Vec<f64>, Vec<f64>)> = (0..20)
+            .map(|i| (vec![i as f64 / 20.0], vec![if i >= 10 { 1.0 } else { 0.0 }]))
+            .collect();
+        let mut rng = RunSeed(1).rng("ensemble", 0);
+        let members = (0..3)
+            .map(|_| Model::new(1, vec![Layer::random(1, 1, Activation::Sigmoid, &mut rng)]).unwrap())
+            .collect();
+        let mut ensemble = Ensemble::new(members, Combination::Stacking);
+
+        ensemble.train_bagging(&data, 50, 1.0, 1, &mut rng);
+        ensemble.fit_stacker(&data, 50, 1.0, &mut rng);
+
+        let evaluation = ensemble.evaluate(&data);
+        assert!(evaluation.accuracy > 0.7);
+        assert_eq!(ensemble.disagreement_all(&data).len(), 20);
+    }
+}
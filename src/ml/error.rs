@@ -0,0 +1,171 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use crate::ml::guard::DivergenceError;
+
+#[derive(Debug)]
+pub enum ModelError {
+    /// A layer without perceptrons.
+    EmptyLayer {
+        layer: usize,
+    },
+    /// A perceptron whose weight count differs from the rest of its layer.
+    InconsistentLayer {
+        layer: usize,
+        neuron: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// `layer` expects `expected` inputs but the previous layer (or the model
+    /// input, for layer 0) provides `found`.
+    LayerMismatch {
+        layer: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A layer received an input of the wrong width.
+    InputSize {
+        layer: usize,
+        neuron: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// The target does not match the width of the model output.
+    TargetSize {
+        expected: usize,
+        found: usize,
+    },
+    /// An operation that would leave the model without layers.
+    NoLayers,
+    /// `layer` is not an index into the model's `layers` layers.
+    LayerIndex {
+        layer: usize,
+        layers: usize,
+    },
+    /// A flat parameter vector whose length does not match the model.
+    ParameterCount {
+        expected: usize,
+        found: usize,
+    },
+    Diverged(DivergenceError),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelError::EmptyLayer { layer } => write!(f, "layer {} has no perceptrons", layer),
+            ModelError::InconsistentLayer { layer, neuron, expected, found } => write!(
+                f,
+                "layer {}, neuron {} has {} weights, but neuron 0 has {}",
+                layer, neuron, found, expected
+            ),
+            ModelError::LayerMismatch { layer: 0, expected, found } => write!(
+                f,
+                "layer 0 expects {} inputs, but the model input width is {}",
+                expected, found
+            ),
+            ModelError::LayerMismatch { layer, expected, found } => write!(
+                f,
+                "layer {} expects {} inputs, but layer {} outputs {}",
+                layer, expected, layer - 1, found
+            ),
+            ModelError::InputSize { layer, neuron, expected, found } => write!(
+                f,
+                "layer {}, neuron {} expects {} inputs, got {}",
+                layer, neuron, expected, found
+            ),
+            ModelError::TargetSize { expected, found } => {
+                write!(f, "model outputs {} values, target has {}", expected, found)
+            }
+            ModelError::NoLayers => write!(f, "model has no layers"),
+            ModelError::LayerIndex { layer, layers } => {
+                write!(f, "no layer {} in a model with {} layers", layer, layers)
+            }
+            ModelError::ParameterCount { expected, found } => {
+                write!(f, "model has {} parameters, got {}", expected, found)
+            }
+            ModelError::Diverged(error) => write!(f, "training diverged: {}", error),
+        }
+    }
+}
+
+impl Error for ModelError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ModelError::Diverged(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<DivergenceError> for ModelError {
+    fn from(error: DivergenceError) -> Self {
+        ModelError::Diverged(error)
+    }
+}
+
+/// Failure to read a saved model file.
+#[derive(Debug)]
+pub enum LoadError {
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    /// A line that does not follow the format. `line` is 1-based, or 0 for
+    /// an unexpected end of file.
+    Format {
+        path: String,
+        line: usize,
+        message: String,
+    },
+    /// The layers read do not form a valid model.
+    Model(ModelError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io { path, source } => write!(f, "{}: {}", path, source),
+            LoadError::Format { path, line: 0, message } => write!(f, "{}: {}", path, message),
+            LoadError::Format { path, line, message } => write!(f, "{}:{}: {}", path, line, message),
+            LoadError::Model(error) => write!(f, "invalid model: {}", error),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io { source, .. } => Some(source),
+            LoadError::Model(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+/// A `SearchSpace` that cannot be searched.
+#[derive(Debug, PartialEq)]
+pub enum SearchError {
+    /// A hyperparameter without candidate values.
+    NoCandidates {
+        parameter: &'static str,
+    },
+    /// A candidate value that cannot be trained with, such as a zero layer
+    /// width or a negative learning rate.
+    InvalidCandidate {
+        parameter: &'static str,
+        value: String,
+    },
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchError::NoCandidates { parameter } => write!(f, "no candidate values for {}", parameter),
+            SearchError::InvalidCandidate { parameter, value } => {
+                write!(f, "invalid {} candidate: {}", parameter, value)
+            }
+        }
+    }
+}
+
+impl Error for SearchError {}
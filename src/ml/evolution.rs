@@ -0,0 +1,406 @@
+use std::ops::Range;
+use std::thread;
+use rand::Rng;
+use crate::ml::model::Model;
+use crate::ml::parameters;
+use crate::ml::predictor::Predictor;
+use crate::ml::summary::Stats;
+
+/// How parents are picked for each child.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Selection {
+    /// The fittest of `size` individuals drawn at random.
+    Tournament { size: usize },
+    /// Uniformly among the `count` fittest individuals.
+    Elite { count: usize },
+}
+
+/// Gradient-free trainer: a population of `Model` clones evolved by
+/// selection, uniform crossover and gaussian mutation of their flat
+/// parameter vectors (see `parameters::flatten`).
+///
+/// Frozen layers are neither re-initialized nor mutated, and pruning masks
+/// are re-applied to every child.
+#[derive(Clone, Debug)]
+pub struct Evolution {
+    pub population_size: usize,
+    pub selection: Selection,
+    /// Fittest individuals copied unchanged into the next generation.
+    pub elitism: usize,
+    /// Probability that a child mixes two parents instead of copying one.
+    pub crossover_rate: f64,
+    /// Probability that each parameter of a child is perturbed.
+    pub mutation_rate: f64,
+    /// Standard deviation of the noise added to a perturbed parameter.
+    pub mutation_scale: f64,
+    /// Number of threads evaluating fitness. Results do not depend on it.
+    pub threads: usize,
+}
+
+/// Individuals and, once evaluated, their fitness (higher is better).
+#[derive(Clone)]
+pub struct Population {
+    pub individuals: Vec<Model>,
+    /// NaN for individuals that have not been evaluated yet. Elites keep
+    /// their fitness from the previous generation.
+    pub fitness: Vec<f64>,
+    /// 0 for the initial population.
+    pub generation: usize,
+}
+
+impl Population {
+    /// Index of the fittest individual.
+    pub fn best_index(&self) -> usize {
+        self.fitness
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map_or(0, |(index, _)| index)
+    }
+
+    pub fn best(&self) -> &Model {
+        &self.individuals[self.best_index()]
+    }
+
+    /// Mean, spread and range of the population's fitness.
+    pub fn fitness_stats(&self) -> Stats {
+        Stats::of(self.fitness.iter())
+    }
+
+    /// Individual indices, fittest first.
+    fn ranking(&self) -> Vec<usize> {
+        let mut ranking: Vec<usize> = (0..self.individuals.len()).collect();
+        ranking.sort_by(|a, b| self.fitness[*b].total_cmp(&self.fitness[*a]));
+        ranking
+    }
+}
+
+impl Default for Evolution {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Evolution {
+    pub fn new() -> Self {
+        Self {
+            population_size: 30,
+            selection: Selection::Tournament { size: 3 },
+            elitism: 1,
+            crossover_rate: 0.5,
+            mutation_rate: 0.1,
+            mutation_scale: 0.1,
+            threads: 1,
+        }
+    }
+
+    pub fn with_population_size(mut self, population_size: usize) -> Self {
+        self.population_size = population_size.max(1);
+        self
+    }
+
+    pub fn with_selection(mut self, selection: Selection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    pub fn with_elitism(mut self, elitism: usize) -> Self {
+        self.elitism = elitism;
+        self
+    }
+
+    pub fn with_crossover_rate(mut self, crossover_rate: f64) -> Self {
+        self.crossover_rate = crossover_rate;
+        self
+    }
+
+    pub fn with_mutation(mut self, rate: f64, scale: f64) -> Self {
+        self.mutation_rate = rate;
+        self.mutation_scale = scale;
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// `template` itself followed by copies with every trainable layer
+    /// re-initialized. Not yet evaluated.
+    pub fn initial_population(&self, template: &Model, rng: &mut impl Rng) -> Population {
+        let mut individuals = vec![template.clone()];
+        while individuals.len() < self.population_size {
+            let mut individual = template.clone();
+            for layer in individual.layers.iter_mut().filter(|layer| layer.trainable) {
+                layer.reinitialize(rng);
+            }
+            individuals.push(individual);
+        }
+        let fitness = vec![f64::NAN; individuals.len()];
+        Population { individuals, fitness, generation: 0 }
+    }
+
+    /// Population to continue from a checkpoint that kept only the best
+    /// individual: `elite` plus fresh copies around it, as in
+    /// `initial_population`, numbered as `generation`.
+    ///
+    /// The rest of the old population is lost, so a resumed run does not
+    /// retrace the generations an uninterrupted one would have bred. It is
+    /// still reproducible for a given checkpoint and `rng`, and never falls
+    /// below the elite's fitness.
+    pub fn resume(&self, elite: &Model, generation: usize, rng: &mut impl Rng) -> Population {
+        Population { generation, ..self.initial_population(elite, rng) }
+    }
+
+    /// Scores every individual not evaluated yet with `fitness`, spread over
+    /// `threads`.
+    pub fn evaluate(&self, population: &mut Population, fitness: &(impl Fn(&Model) -> f64 + Sync)) {
+        population.fitness.resize(population.individuals.len(), f64::NAN);
+        let pending: Vec<usize> =
+            population.fitness.iter().enumerate().filter(|(_, f)| f.is_nan()).map(|(i, _)| i).collect();
+        let chunk_size = pending.len().div_ceil(self.threads.max(1)).max(1);
+        // NaN fitness ranks last
+        let score = |model: &Model| Some(fitness(model)).filter(|f| !f.is_nan()).unwrap_or(f64::NEG_INFINITY);
+
+        let individuals = &population.individuals;
+        let scores: Vec<(usize, f64)> = thread::scope(|scope| {
+            let workers: Vec<_> = pending
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || chunk.iter().map(|&i| (i, score(&individuals[i]))).collect::<Vec<_>>())
+                })
+                .collect();
+            workers.into_iter().flat_map(|worker| worker.join().expect("fitness evaluation panicked")).collect()
+        });
+        for (index, score) in scores {
+            population.fitness[index] = score;
+        }
+    }
+
+    /// Breeds the next generation from an evaluated one. Only the elites,
+    /// which are copied unchanged, keep their fitness.
+    pub fn next_generation(&self, population: &Population, rng: &mut impl Rng) -> Population {
+        let ranking = population.ranking();
+        let template = population.best();
+        let trainable = trainable_ranges(template);
+
+        let elites = &ranking[..self.elitism.min(self.population_size).min(ranking.len())];
+        let mut individuals: Vec<Model> = elites.iter().map(|&i| population.individuals[i].clone()).collect();
+        let mut fitness: Vec<f64> = elites.iter().map(|&i| population.fitness[i]).collect();
+        while individuals.len() < self.population_size {
+            let first = parameters::flatten(&population.individuals[self.select(population, &ranking, rng)].layers);
+            let mut child = if rng.random::<f64>() < self.crossover_rate {
+                let second = parameters::flatten(&population.individuals[self.select(population, &ranking, rng)].layers);
+                first.iter().zip(&second).map(|(a, b)| if rng.random() { *a } else { *b }).collect()
+            } else {
+                first
+            };
+
+            for range in &trainable {
+                for parameter in &mut child[range.clone()] {
+                    if rng.random::<f64>() < self.mutation_rate {
+                        *parameter += self.mutation_scale * gaussian(rng);
+                    }
+                }
+            }
+
+            let mut individual = template.clone();
+            individual.set_parameters(&child).expect("children share the template's shape");
+            individuals.push(individual);
+            fitness.push(f64::NAN);
+        }
+
+        Population { individuals, fitness, generation: population.generation + 1 }
+    }
+
+    fn select(&self, population: &Population, ranking: &[usize], rng: &mut impl Rng) -> usize {
+        let len = population.individuals.len();
+        match self.selection {
+            Selection::Tournament { size } => (0..size.max(1))
+                .map(|_| rng.random_range(0..len))
+                .max_by(|a, b| population.fitness[*a].total_cmp(&population.fitness[*b]))
+                .unwrap_or(0),
+            Selection::Elite { count } => ranking[rng.random_range(0..count.clamp(1, len))],
+        }
+    }
+}
+
+/// Fitness as classification accuracy on `data`.
+pub fn accuracy(data: &[(Vec<f64>, Vec<f64>)]) -> impl Fn(&Model) -> f64 + Sync + '_ {
+    move |model| model.evaluate(data).accuracy
+}
+
+/// Fitness as the negated mean loss on `data`.
+pub fn negative_loss(data: &[(Vec<f64>, Vec<f64>)]) -> impl Fn(&Model) -> f64 + Sync + '_ {
+    move |model| -model.mean_loss(data)
+}
+
+/// Ranges of the flat parameter vector that belong to trainable layers.
+fn trainable_ranges(model: &Model) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for layer in &model.layers {
+        let end = start + parameters::count(std::slice::from_ref(layer));
+        if layer.trainable {
+            ranges.push(start..end);
+        }
+        start = end;
+    }
+    ranges
+}
+
+/// Standard normal sample (Box-Muller).
+fn gaussian(rng: &mut impl Rng) -> f64 {
+    let u1: f64 = 1.0 - rng.random::<f64>();
+    let u2: f64 = rng.random();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::activation::Activation;
+    use crate::ml::layer::Layer;
+    use crate::ml::seed::RunSeed;
+
+    fn data() -> Vec<(Vec<f64>, Vec<f64>)> {
+        // XOR-like quadrants, which no single linear unit separates
+        (0..40)
+            .map(|i| {
+                let x = (i % 8) as f64 / 7.0;
+                let y = (i / 8) as f64 / 4.0;
+                let label = if (x > 0.5) != (y > 0.5) { 1.0 } else { 0.0 };
+                (vec![x, y], vec![label])
+            })
+            .collect()
+    }
+
+    fn template(seed: u64) -> Model {
+        let mut rng = RunSeed(seed).rng("init", 0);
+        Model::new(2, vec![
+            Layer::random(2, 6, Activation::ReLU, &mut rng),
+            Layer::random(6, 1, Activation::Sigmoid, &mut rng),
+        ])
+        .unwrap()
+    }
+
+    /// Evolves `template` for `generations`, passing every evaluated
+    /// population to `on_generation`, and returns the last one.
+    fn run(
+        evolution: &Evolution,
+        template: &Model,
+        generations: usize,
+        fitness: &(impl Fn(&Model) -> f64 + Sync),
+        rng: &mut impl Rng,
+        mut on_generation: impl FnMut(&Population),
+    ) -> Population {
+        let mut population = evolution.initial_population(template, rng);
+        evolution.evaluate(&mut population, fitness);
+        on_generation(&population);
+        for _ in 0..generations {
+            population = evolution.next_generation(&population, rng);
+            evolution.evaluate(&mut population, fitness);
+            on_generation(&population);
+        }
+        population
+    }
+
+    #[test]
+    fn test_evolution_improves_fitness() {
+        let data = data();
+        let fitness = negative_loss(&data);
+        let evolution = Evolution::new().with_population_size(20).with_mutation(0.3, 0.3).with_threads(2);
+
+        let mut best = Vec::new();
+        let population = run(&evolution, &template(1), 40, &fitness, &mut RunSeed(1).rng("evolution", 0), |population| {
+            best.push(population.fitness_stats().max)
+        });
+
+        assert_eq!(population.generation, 40);
+        assert_eq!(best.len(), 41);
+        // Elitism keeps the best individual, so the best fitness never drops
+        assert!(best.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert!(best[40] > best[0]);
+        assert_eq!(fitness(population.best()), best[40]);
+    }
+
+    #[test]
+    fn test_selection_and_frozen_layers() {
+        let data = data();
+        let mut template = template(2);
+        template.freeze(0);
+        let frozen = template.layers[0].perceptrons[0].weights.clone();
+
+        let evolution = Evolution::new()
+            .with_population_size(8)
+            .with_selection(Selection::Elite { count: 2 })
+            .with_elitism(0)
+            .with_mutation(1.0, 0.5);
+        let mut rng = RunSeed(2).rng("evolution", 0);
+        let population = run(&evolution, &template, 3, &accuracy(&data), &mut rng, |_| {});
+
+        assert_eq!(population.individuals.len(), 8);
+        for individual in &population.individuals {
+            assert_eq!(individual.layers[0].perceptrons[0].weights, frozen);
+        }
+    }
+
+    #[test]
+    fn test_elites_are_not_reevaluated() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let data = data();
+        let evaluations = AtomicUsize::new(0);
+        let fitness = |model: &Model| {
+            evaluations.fetch_add(1, Ordering::Relaxed);
+            -model.mean_loss(&data)
+        };
+        let evolution = Evolution::new().with_population_size(6).with_elitism(2).with_threads(2);
+        let population = run(&evolution, &template(4), 3, &fitness, &mut RunSeed(4).rng("evolution", 0), |_| {});
+
+        assert_eq!(evaluations.load(Ordering::Relaxed), 6 + 3 * 4);
+        assert!(population.fitness.iter().all(|f| !f.is_nan()));
+    }
+
+    #[test]
+    fn test_same_seed_same_population() {
+        let data = data();
+        let evolution = Evolution::new().with_population_size(6);
+        let best_parameters = |threads| {
+            let mut rng = RunSeed(3).rng("evolution", 0);
+            let evolution = evolution.clone().with_threads(threads);
+            run(&evolution, &template(3), 5, &negative_loss(&data), &mut rng, |_| {}).best().parameters()
+        };
+        assert_eq!(best_parameters(1), best_parameters(3));
+    }
+
+    #[test]
+    fn test_resume_from_elite() {
+        let data = data();
+        let fitness = negative_loss(&data);
+        let evolution = Evolution::new().with_population_size(8).with_mutation(0.3, 0.3);
+        let seed = RunSeed(5);
+        let mut population = evolution.initial_population(&template(5), &mut seed.rng("population", 0));
+        for generation in 0..4 {
+            evolution.evaluate(&mut population, &fitness);
+            population = evolution.next_generation(&population, &mut seed.rng("evolution", generation));
+        }
+        evolution.evaluate(&mut population, &fitness);
+        let elite = population.best().clone();
+
+        let resume = || {
+            let mut resumed = evolution.resume(&elite, 4, &mut seed.rng("population", 4));
+            evolution.evaluate(&mut resumed, &fitness);
+            resumed
+        };
+        let resumed = resume();
+        assert_eq!(resumed.generation, 4);
+        assert_eq!(resumed.individuals.len(), 8);
+        assert_eq!(resumed.individuals[0].parameters(), elite.parameters());
+        assert!(resumed.fitness_stats().max >= fitness(&elite));
+
+        let again = resume();
+        let parameters = |population: &Population| population.individuals.iter().map(Model::parameters).collect::<Vec<_>>();
+        assert_eq!(parameters(&resumed), parameters(&again));
+    }
+}
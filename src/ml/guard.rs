@@ -0,0 +1,101 @@
+use std::error::Error;
+use std::fmt;
+use crate::ml::layer::LayerCache;
+use crate::ml::model::Model;
+use crate::ml::training::TrainingContext;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NumericStage {
+    Activation,
+    Gradient,
+    Parameter,
+}
+
+impl fmt::Display for NumericStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NumericStage::Activation => write!(f, "activation"),
+            NumericStage::Gradient => write!(f, "gradient"),
+            NumericStage::Parameter => write!(f, "parameter"),
+        }
+    }
+}
+
+/// Location of the first NaN or infinite value found in a model.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NonFinite {
+    pub stage: NumericStage,
+    pub layer: usize,
+    pub neuron: usize,
+    pub value: f64,
+}
+
+/// Checks the cached activations and the last gradients in `context`, then
+/// the parameters `model` would have after applying those gradients, each
+/// layer at the rate returned by `learning_rate` for its index, so that a
+/// failing step can be rejected before it changes anything.
+pub fn find_non_finite_update(
+    model: &Model,
+    context: &TrainingContext,
+    learning_rate: impl Fn(usize) -> f64,
+) -> Option<NonFinite> {
+    let caches = &context.caches;
+    find_in(caches, NumericStage::Activation, LayerCache::find_non_finite_activation)
+        .or_else(|| find_in(caches, NumericStage::Gradient, LayerCache::find_non_finite_gradient))
+        .or_else(|| {
+            model.layers.iter().zip(caches).enumerate().find_map(|(index, (layer, cache))| {
+                layer.find_non_finite_update(cache, learning_rate(index)).map(|(neuron, value)| NonFinite {
+                    stage: NumericStage::Parameter,
+                    layer: index,
+                    neuron,
+                    value,
+                })
+            })
+        })
+}
+
+fn find_in<T>(
+    items: &[T],
+    stage: NumericStage,
+    check: impl Fn(&T) -> Option<(usize, f64)>,
+) -> Option<NonFinite> {
+    items.iter().enumerate().find_map(|(index, item)| {
+        check(item).map(|(neuron, value)| NonFinite { stage, layer: index, neuron, value })
+    })
+}
+
+/// Training stopped because a step produced a NaN or infinite value.
+pub struct DivergenceError {
+    pub location: NonFinite,
+    pub epoch: usize,
+    pub sample: usize,
+    /// The model as it was before the failing step.
+    pub last_finite: Box<Model>,
+}
+
+impl fmt::Debug for DivergenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DivergenceError")
+            .field("location", &self.location)
+            .field("epoch", &self.epoch)
+            .field("sample", &self.sample)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for DivergenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "non-finite {} ({}) in layer {}, neuron {} at epoch {}, sample {}",
+            self.location.stage,
+            self.location.value,
+            self.location.layer,
+            self.location.neuron,
+            self.epoch,
+            self.sample,
+        )
+    }
+}
+
+impl Error for DivergenceError {}
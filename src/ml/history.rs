@@ -0,0 +1,203 @@
+use std::io;
+use std::path::Path;
+use crate::ml::model::TrainStep;
+
+/// Summary of one training epoch. Validation and accuracy fields are `None`
+/// when they were not measured.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpochRecord {
+    /// 1-based number of the epoch.
+    pub epoch: usize,
+    pub train_loss: f64,
+    pub validation_loss: Option<f64>,
+    pub train_accuracy: Option<f64>,
+    pub validation_accuracy: Option<f64>,
+    pub learning_rate: f64,
+    /// Mean pre-clip gradient norm over the epoch's steps.
+    pub gradient_norm: f64,
+    /// Wall time of the epoch.
+    pub seconds: f64,
+}
+
+/// One optimizer step within an epoch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BatchRecord {
+    pub epoch: usize,
+    pub batch: usize,
+    pub loss: f64,
+    pub gradient_norm: f64,
+}
+
+/// Per-epoch (and optionally per-batch) record of a training run.
+#[derive(Clone, Debug, Default)]
+pub struct TrainingHistory {
+    pub epochs: Vec<EpochRecord>,
+    pub batches: Vec<BatchRecord>,
+    /// When set, `record_batch` keeps every step; otherwise it is a no-op.
+    pub record_batches: bool,
+}
+
+const EPOCH_COLUMNS: [&str; 8] = [
+    "epoch",
+    "train_loss",
+    "validation_loss",
+    "train_accuracy",
+    "validation_accuracy",
+    "learning_rate",
+    "gradient_norm",
+    "seconds",
+];
+
+const BATCH_COLUMNS: [&str; 4] = ["epoch", "batch", "loss", "gradient_norm"];
+
+impl TrainingHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_batches(mut self) -> Self {
+        self.record_batches = true;
+        self
+    }
+
+    pub fn record_epoch(&mut self, record: EpochRecord) {
+        self.epochs.push(record);
+    }
+
+    pub fn record_batch(&mut self, epoch: usize, batch: usize, step: &TrainStep) {
+        if self.record_batches {
+            self.batches.push(BatchRecord { epoch, batch, loss: step.loss, gradient_norm: step.gradient_norm });
+        }
+    }
+
+    pub fn last(&self) -> Option<&EpochRecord> {
+        self.epochs.last()
+    }
+
+    pub fn train_losses(&self) -> Vec<f64> {
+        self.epochs.iter().map(|record| record.train_loss).collect()
+    }
+
+    pub fn validation_losses(&self) -> Vec<f64> {
+        self.epochs.iter().filter_map(|record| record.validation_loss).collect()
+    }
+
+    /// Writes one CSV row per epoch. Missing values are left empty.
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(EPOCH_COLUMNS)?;
+        for record in &self.epochs {
+            writer.write_record(epoch_values(record).iter().map(|value| value.map_or(String::new(), |v| v.to_string())))?;
+        }
+        writer.flush()
+    }
+
+    /// Writes one CSV row per recorded batch.
+    pub fn write_batches_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(BATCH_COLUMNS)?;
+        for record in &self.batches {
+            writer.write_record(batch_values(record).map(|value| value.to_string()))?;
+        }
+        writer.flush()
+    }
+
+    /// `{"epochs": [...], "batches": [...]}` with one object per record.
+    /// Missing and non-finite values become `null`.
+    pub fn to_json(&self) -> String {
+        let epochs: Vec<String> = self
+            .epochs
+            .iter()
+            .map(|record| json_object(&EPOCH_COLUMNS, &epoch_values(record)))
+            .collect();
+        let batches: Vec<String> = self
+            .batches
+            .iter()
+            .map(|record| json_object(&BATCH_COLUMNS, &batch_values(record).map(Some)))
+            .collect();
+        format!("{{\"epochs\": [{}], \"batches\": [{}]}}", epochs.join(", "), batches.join(", "))
+    }
+
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_json())
+    }
+}
+
+fn epoch_values(record: &EpochRecord) -> [Option<f64>; 8] {
+    [
+        Some(record.epoch as f64),
+        Some(record.train_loss),
+        record.validation_loss,
+        record.train_accuracy,
+        record.validation_accuracy,
+        Some(record.learning_rate),
+        Some(record.gradient_norm),
+        Some(record.seconds),
+    ]
+}
+
+fn batch_values(record: &BatchRecord) -> [f64; 4] {
+    [record.epoch as f64, record.batch as f64, record.loss, record.gradient_norm]
+}
+
+fn json_object(keys: &[&str], values: &[Option<f64>]) -> String {
+    let fields: Vec<String> = keys
+        .iter()
+        .zip(values)
+        .map(|(key, value)| match value {
+            Some(value) if value.is_finite() => format!("\"{}\": {}", key, value),
+            _ => format!("\"{}\": null", key),
+        })
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> TrainingHistory {
+        let mut history = TrainingHistory::new().with_batches();
+        history.record_epoch(EpochRecord {
+            epoch: 1,
+            train_loss: 0.5,
+            validation_loss: Some(0.25),
+            train_accuracy: None,
+            validation_accuracy: Some(0.75),
+            learning_rate: 0.01,
+            gradient_norm: 2.0,
+            seconds: 1.5,
+        });
+        history.record_batch(1, 0, &TrainStep { loss: 0.5, gradient_norm: f64::NAN });
+        history
+    }
+
+    #[test]
+    fn test_to_json() {
+        assert_eq!(
+            history().to_json(),
+            "{\"epochs\": [{\"epoch\": 1, \"train_loss\": 0.5, \"validation_loss\": 0.25, \"train_accuracy\": null, \
+             \"validation_accuracy\": 0.75, \"learning_rate\": 0.01, \"gradient_norm\": 2, \"seconds\": 1.5}], \
+             \"batches\": [{\"epoch\": 1, \"batch\": 0, \"loss\": 0.5, \"gradient_norm\": null}]}"
+        );
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let path = std::env::temp_dir().join("training_history.csv");
+        history().write_csv(path.to_str().unwrap()).unwrap();
+
+        let table = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], EPOCH_COLUMNS.join(","));
+        assert_eq!(lines[1], "1,0.5,0.25,,0.75,0.01,2,1.5");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_batches_only_when_enabled() {
+        let mut history = TrainingHistory::new();
+        history.record_batch(1, 0, &TrainStep { loss: 0.5, gradient_norm: 1.0 });
+        assert!(history.batches.is_empty());
+    }
+}
@@ -1,43 +1,267 @@
+use rand::Rng;
 use crate::ml::perceptron::Perceptron;
 use crate::ml::activation::Activation;
+use crate::ml::simd;
 
 #[derive(Clone)]
 pub struct Layer {
     pub perceptrons: Vec<Perceptron>,
     pub activation: Activation,
-    // Cached values from forward pass (needed for backprop)
-    last_input: Vec<f64>,
-    last_weighted_sums: Vec<f64>,
+    /// Pruning mask, `false` for weights that are fixed at zero.
+    pub mask: Option<Vec<Vec<bool>>>,
+    /// Frozen layers still pass gradients to the layer before them but
+    /// collect no weight gradients and are never updated.
+    pub trainable: bool,
+}
+
+/// Per-layer training state: the values cached by `Layer::forward_cached`
+/// for backprop and the gradients applied by `Layer::apply_gradients`.
+///
+/// The buffers are reused from one step to the next, so once they have
+/// grown to the layer's size training does not allocate.
+#[derive(Clone, Default)]
+pub struct LayerCache {
+    input: Vec<f64>,
+    weighted_sums: Vec<f64>,
+    output: Vec<f64>,
+    input_gradients: Vec<f64>,
+    weight_gradients: Vec<Vec<f64>>,
+    bias_gradients: Vec<f64>,
 }
 
 impl Layer {
     pub fn new(perceptrons: Vec<Perceptron>, activation: Activation) -> Self {
-        Self { perceptrons, activation, last_input: Vec::new(), last_weighted_sums: Vec::new() }
-    }
-    pub fn forward(&mut self, input: &[f64]) -> Vec<f64> {
-        let mut output = Vec::new();
-        self.last_input = input.to_vec();
-        self.last_weighted_sums = Vec::new(); 
-        for perceptron in &self.perceptrons {
-            let weighted_sum = perceptron.forward(input);
-            self.last_weighted_sums.push(weighted_sum);
-            let activated = self.activation.activate(weighted_sum);
-            output.push(activated);
+        Self {
+            perceptrons,
+            activation,
+            mask: None,
+            trainable: true,
+        }
+    }
+    /// Dense layer with weights drawn from [-0.5, 0.5) and biases from
+    /// [-0.1, 0.1).
+    pub fn random(num_inputs: usize, num_neurons: usize, activation: Activation, rng: &mut impl Rng) -> Self {
+        let mut perceptrons = Vec::new();
+
+        for _ in 0..num_neurons {
+            let weights: Vec<f64> = (0..num_inputs)
+                .map(|_| rng.random_range(-0.5..0.5))
+                .collect();
+            let bias = rng.random_range(-0.1..0.1);
+
+            perceptrons.push(Perceptron::new(weights, bias));
         }
+
+        Layer::new(perceptrons, activation)
+    }
+
+    pub fn forward(&self, input: &[f64]) -> Vec<f64> {
+        let mut output: Vec<f64> = self.perceptrons.iter().map(|perceptron| perceptron.forward(input)).collect();
+        self.activation.activate_all(&mut output);
         output
     }
-    pub fn backward(&mut self, output_gradients: &[f64], learning_rate: f64) -> Vec<f64> {
-        let mut input_gradients = vec![0.0; self.last_input.len()];
-        for i in 0..self.perceptrons.len() {
-            let activation_derivative = self.activation.derivative(self.last_weighted_sums[i]);
+
+    /// Like `forward`, but keeps the input and weighted sums in `cache` for
+    /// the backward pass and returns the activations stored there.
+    pub fn forward_cached<'a>(&self, input: &[f64], cache: &'a mut LayerCache) -> &'a [f64] {
+        cache.input.clear();
+        cache.input.extend_from_slice(input);
+        cache.weighted_sums.clear();
+        cache.weighted_sums.extend(self.perceptrons.iter().map(|perceptron| perceptron.forward(input)));
+        cache.output.clear();
+        cache.output.extend_from_slice(&cache.weighted_sums);
+        self.activation.activate_all(&mut cache.output);
+        &cache.output
+    }
+    /// Number of inputs expected by the first perceptron.
+    pub fn input_width(&self) -> usize {
+        self.perceptrons.first().map_or(0, |perceptron| perceptron.weights.len())
+    }
+
+    /// First neuron whose weight count differs from `input_width`, with its
+    /// expected width.
+    pub fn input_mismatch(&self, input_width: usize) -> Option<(usize, usize)> {
+        self.perceptrons
+            .iter()
+            .position(|perceptron| perceptron.weights.len() != input_width)
+            .map(|neuron| (neuron, self.perceptrons[neuron].weights.len()))
+    }
+    pub fn backward<'a>(&mut self, cache: &'a mut LayerCache, output_gradients: &[f64], learning_rate: f64) -> &'a [f64] {
+        self.compute_gradients(cache, output_gradients);
+        self.apply_gradients(cache, learning_rate);
+        &cache.input_gradients
+    }
+
+    /// Stores the weight and bias gradients for the forward pass cached in
+    /// `cache` and returns the gradients with respect to the layer input.
+    /// Weights are left untouched until `apply_gradients` is called.
+    pub fn compute_gradients<'a>(&self, cache: &'a mut LayerCache, output_gradients: &[f64]) -> &'a [f64] {
+        self.zero_gradients(cache);
+        self.accumulate_gradients(cache, output_gradients)
+    }
+
+    /// Resets the gradients in `cache` to zero, sized to the current weights.
+    pub fn zero_gradients(&self, cache: &mut LayerCache) {
+        cache.weight_gradients.resize(self.perceptrons.len(), Vec::new());
+        for (gradients, perceptron) in cache.weight_gradients.iter_mut().zip(&self.perceptrons) {
+            gradients.clear();
+            gradients.resize(perceptron.weights.len(), 0.0);
+        }
+        cache.bias_gradients.clear();
+        cache.bias_gradients.resize(self.perceptrons.len(), 0.0);
+    }
+
+    /// Like `compute_gradients`, but adds to the stored gradients so that
+    /// several samples can be combined into one mini-batch update.
+    pub fn accumulate_gradients<'a>(&self, cache: &'a mut LayerCache, output_gradients: &[f64]) -> &'a [f64] {
+        cache.input_gradients.clear();
+        cache.input_gradients.resize(cache.input.len(), 0.0);
+        for (i, perceptron) in self.perceptrons.iter().enumerate() {
+            let activation_derivative = self.activation.derivative(cache.weighted_sums[i]);
             let delta = output_gradients[i] * activation_derivative;
-            for j in 0..self.perceptrons[i].weights.len() {
-                input_gradients[j] += delta * self.perceptrons[i].weights[j];
-                self.perceptrons[i].weights[j] -= learning_rate * delta * self.last_input[j];
+            // only the inputs the forward pass actually multiplied, as there
+            let len = perceptron.weights.len().min(cache.input.len());
+            simd::axpy(delta, &perceptron.weights[..len], &mut cache.input_gradients[..len]);
+            if self.trainable {
+                simd::axpy(delta, &cache.input[..len], &mut cache.weight_gradients[i][..len]);
+                cache.bias_gradients[i] += delta;
             }
-            self.perceptrons[i].bias -= learning_rate * delta;
         }
-        input_gradients
+        &cache.input_gradients
+    }
+
+    /// Adds the L2 penalty gradient `weight_decay * w` to every weight
+    /// gradient. Biases are not decayed.
+    pub fn add_weight_decay(&self, cache: &mut LayerCache, weight_decay: f64) {
+        if !self.trainable {
+            return;
+        }
+        for (gradients, perceptron) in cache.weight_gradients.iter_mut().zip(&self.perceptrons) {
+            simd::axpy(weight_decay, &perceptron.weights, gradients);
+        }
+    }
+
+    pub fn apply_gradients(&mut self, cache: &LayerCache, learning_rate: f64) {
+        if !self.trainable {
+            return;
+        }
+        for (i, perceptron) in self.perceptrons.iter_mut().enumerate() {
+            simd::axpy(-learning_rate, &cache.weight_gradients[i], &mut perceptron.weights);
+            perceptron.bias -= learning_rate * cache.bias_gradients[i];
+        }
+        self.apply_mask();
+    }
+
+    /// Draws new random parameters as `Layer::random` would, keeping the
+    /// shape, activation, pruning mask and trainable flag.
+    pub fn reinitialize(&mut self, rng: &mut impl Rng) {
+        let fresh = Layer::random(self.input_width(), self.perceptrons.len(), self.activation, rng);
+        self.perceptrons = fresh.perceptrons;
+        self.apply_mask();
+    }
+
+    /// Zeroes every weight removed by the pruning mask.
+    pub fn apply_mask(&mut self) {
+        if let Some(mask) = &self.mask {
+            for (perceptron, keep) in self.perceptrons.iter_mut().zip(mask) {
+                for (weight, keep) in perceptron.weights.iter_mut().zip(keep) {
+                    if !keep {
+                        *weight = 0.0;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Drops neuron `index` and its row of the pruning mask.
+    pub fn remove_neuron(&mut self, index: usize) {
+        self.perceptrons.remove(index);
+        if let Some(mask) = &mut self.mask {
+            mask.remove(index);
+        }
+    }
+
+    /// Drops input `index` from every perceptron and the pruning mask.
+    pub fn remove_input(&mut self, index: usize) {
+        for perceptron in &mut self.perceptrons {
+            perceptron.weights.remove(index);
+        }
+        if let Some(mask) = &mut self.mask {
+            for row in mask {
+                row.remove(index);
+            }
+        }
+    }
+
+    /// First neuron with a NaN or infinite weight or bias.
+    pub fn find_non_finite_parameter(&self) -> Option<(usize, f64)> {
+        self.perceptrons.iter().enumerate().find_map(|(i, perceptron)| {
+            perceptron.weights.iter().chain(std::iter::once(&perceptron.bias)).find(|w| !w.is_finite()).map(|w| (i, *w))
+        })
+    }
+
+    /// First neuron with a weight or bias that `apply_gradients` would make
+    /// NaN or infinite, without changing anything.
+    pub fn find_non_finite_update(&self, cache: &LayerCache, learning_rate: f64) -> Option<(usize, f64)> {
+        if !self.trainable {
+            return self.find_non_finite_parameter();
+        }
+        self.perceptrons.iter().enumerate().find_map(|(i, perceptron)| {
+            let weights = perceptron.weights.iter().zip(&cache.weight_gradients[i]);
+            let bias = std::iter::once((&perceptron.bias, &cache.bias_gradients[i]));
+            weights.chain(bias).map(|(w, g)| w - learning_rate * g).find(|w| !w.is_finite()).map(|w| (i, w))
+        })
+    }
+}
+
+impl LayerCache {
+    /// Activations of the last `Layer::forward_cached` call.
+    pub fn output(&self) -> &[f64] {
+        &self.output
+    }
+
+    /// Input gradients of the last `Layer::accumulate_gradients` call.
+    pub fn input_gradients(&self) -> &[f64] {
+        &self.input_gradients
+    }
+
+    /// Stored weight gradients, one row per neuron.
+    pub fn weight_gradients(&self) -> &[Vec<f64>] {
+        &self.weight_gradients
+    }
+
+    pub fn bias_gradients(&self) -> &[f64] {
+        &self.bias_gradients
+    }
+
+    pub fn gradient_squared_norm(&self) -> f64 {
+        let weights: f64 = self.weight_gradients.iter().flatten().map(|g| g * g).sum();
+        let biases: f64 = self.bias_gradients.iter().map(|g| g * g).sum();
+        weights + biases
+    }
+
+    pub fn clamp_gradients(&mut self, max_value: f64) {
+        for gradient in self.weight_gradients.iter_mut().flatten().chain(self.bias_gradients.iter_mut()) {
+            *gradient = gradient.clamp(-max_value, max_value);
+        }
+    }
+
+    pub fn scale_gradients(&mut self, factor: f64) {
+        for gradient in self.weight_gradients.iter_mut().flatten().chain(self.bias_gradients.iter_mut()) {
+            *gradient *= factor;
+        }
+    }
+
+    /// First neuron whose cached weighted sum is NaN or infinite.
+    pub fn find_non_finite_activation(&self) -> Option<(usize, f64)> {
+        self.weighted_sums.iter().copied().enumerate().find(|(_, sum)| !sum.is_finite())
+    }
+
+    /// First neuron with a NaN or infinite weight or bias gradient.
+    pub fn find_non_finite_gradient(&self) -> Option<(usize, f64)> {
+        self.weight_gradients.iter().zip(&self.bias_gradients).enumerate().find_map(|(i, (weights, bias))| {
+            weights.iter().chain(std::iter::once(bias)).find(|g| !g.is_finite()).map(|g| (i, *g))
+        })
     }
 }
 
@@ -47,7 +271,7 @@ mod tests {
 
     #[test]
     fn test_layer_forward() {
-        let mut layer = Layer::new(vec![Perceptron::new(vec![0.0], 0.0)], Activation::ReLU);
+        let layer = Layer::new(vec![Perceptron::new(vec![0.0], 0.0)], Activation::ReLU);
         let input = vec![0.0, 0.0];
         let output = layer.forward(&input);
         assert_eq!(output, vec![0.0]);
@@ -56,7 +280,7 @@ mod tests {
     #[test]
     fn test_layer_forward_with_multiple_perceptrons() {
         let perceptron = Perceptron::new(vec![0.5, -0.3], 0.1);
-        let mut layer = Layer::new(vec![perceptron], Activation::ReLU);
+        let layer = Layer::new(vec![perceptron], Activation::ReLU);
         let input = vec![2.0, 4.0];
         let output = layer.forward(&input);
         assert_eq!(output, vec![0.0]);
@@ -65,7 +289,7 @@ mod tests {
     #[test]
     fn test_single_perceptron_positive_output() {
         let perceptron = Perceptron::new(vec![0.5, 0.3], 0.1);
-        let mut layer = Layer::new(vec![perceptron], Activation::ReLU);
+        let layer = Layer::new(vec![perceptron], Activation::ReLU);
         let input = vec![2.0, 4.0];
         let output = layer.forward(&input);
         assert!((output[0] - 2.3).abs() < 1e-10);
@@ -81,7 +305,8 @@ mod tests {
         let learning_rate = 0.1;
 
         // Forward pass
-        let output = layer.forward(&input);
+        let mut cache = LayerCache::default();
+        let output = layer.forward_cached(&input, &mut cache).to_vec();
 
         // Save old weights
         let old_weight_0 = layer.perceptrons[0].weights[0];
@@ -90,7 +315,7 @@ mod tests {
 
         // Backward pass with gradient of 1.0
         let output_gradients = vec![1.0];
-        let _input_gradients = layer.backward(&output_gradients, learning_rate);
+        let _input_gradients = layer.backward(&mut cache, &output_gradients, learning_rate);
 
         // Weights should have changed
         assert_ne!(layer.perceptrons[0].weights[0], old_weight_0);
@@ -113,7 +338,8 @@ mod tests {
         let learning_rate = 0.1;
 
         // Forward: weighted_sum = 0.5 * 2.0 = 1.0, ReLU(1.0) = 1.0
-        let output = layer.forward(&input);
+        let mut cache = LayerCache::default();
+        let output = layer.forward_cached(&input, &mut cache);
         assert!((output[0] - 1.0).abs() < 1e-10);
 
         // Backward with gradient = 0.5
@@ -121,7 +347,7 @@ mod tests {
         // weight_update = learning_rate * delta * input = 0.1 * 0.5 * 2.0 = 0.1
         // new_weight = 0.5 - 0.1 = 0.4
         let output_gradients = vec![0.5];
-        layer.backward(&output_gradients, learning_rate);
+        layer.backward(&mut cache, &output_gradients, learning_rate);
 
         assert!((layer.perceptrons[0].weights[0] - 0.4).abs() < 1e-10);
     }
@@ -134,10 +360,11 @@ mod tests {
         let mut layer = Layer::new(vec![p1, p2], Activation::ReLU);
 
         let input = vec![1.0, 1.0];
-        layer.forward(&input);
+        let mut cache = LayerCache::default();
+        layer.forward_cached(&input, &mut cache);
 
         let output_gradients = vec![1.0, 1.0];
-        let input_gradients = layer.backward(&output_gradients, 0.1);
+        let input_gradients = layer.backward(&mut cache, &output_gradients, 0.1);
 
         // Should return gradients for each input
         assert_eq!(input_gradients.len(), 2);
@@ -149,4 +376,125 @@ mod tests {
         // input_gradient[1] = 1.0 * 0.2 + 1.0 * 0.4 = 0.6
         assert!((input_gradients[1] - 0.6).abs() < 1e-10);
     }
+
+    #[test]
+    fn test_compute_gradients_leaves_weights_until_applied() {
+        let perceptron = Perceptron::new(vec![0.5], 0.0);
+        let mut layer = Layer::new(vec![perceptron], Activation::ReLU);
+
+        let mut cache = LayerCache::default();
+        layer.forward_cached(&[2.0], &mut cache);
+        layer.compute_gradients(&mut cache, &[0.5]);
+        assert_eq!(layer.perceptrons[0].weights[0], 0.5);
+
+        // Same numbers as test_backward_manual_calculation
+        layer.apply_gradients(&cache, 0.1);
+        assert!((layer.perceptrons[0].weights[0] - 0.4).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_clamp_and_scale_gradients() {
+        let perceptron = Perceptron::new(vec![1.0, 1.0], 0.0);
+        let layer = Layer::new(vec![perceptron], Activation::ReLU);
+
+        // weight gradients = [3.0, 4.0], bias gradient = 1.0
+        let mut cache = LayerCache::default();
+        layer.forward_cached(&[3.0, 4.0], &mut cache);
+        layer.compute_gradients(&mut cache, &[1.0]);
+        assert!((cache.gradient_squared_norm() - 26.0).abs() < 1e-10);
+
+        cache.clamp_gradients(2.0);
+        assert!((cache.gradient_squared_norm() - 9.0).abs() < 1e-10);
+
+        cache.scale_gradients(0.5);
+        assert!((cache.gradient_squared_norm() - 2.25).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_find_non_finite() {
+        let p1 = Perceptron::new(vec![1.0], 0.0);
+        let p2 = Perceptron::new(vec![f64::INFINITY], 0.0);
+        let mut layer = Layer::new(vec![p1, p2], Activation::Sigmoid);
+
+        assert_eq!(layer.find_non_finite_parameter().map(|(i, _)| i), Some(1));
+
+        // inf * 0.0 = NaN weighted sum for the second neuron
+        let mut cache = LayerCache::default();
+        layer.forward_cached(&[0.0], &mut cache);
+        assert_eq!(cache.find_non_finite_activation().map(|(i, _)| i), Some(1));
+
+        layer.perceptrons[1].weights[0] = 1.0;
+        layer.forward_cached(&[1.0], &mut cache);
+        assert!(cache.find_non_finite_activation().is_none());
+        layer.compute_gradients(&mut cache, &[f64::NAN, 1.0]);
+        assert_eq!(cache.find_non_finite_gradient().map(|(i, _)| i), Some(0));
+    }
+
+    #[test]
+    fn test_masked_weights_stay_zero() {
+        let perceptron = Perceptron::new(vec![0.5, 0.5], 0.0);
+        let mut layer = Layer::new(vec![perceptron], Activation::ReLU);
+        layer.mask = Some(vec![vec![true, false]]);
+        layer.apply_mask();
+
+        let mut cache = LayerCache::default();
+        layer.forward_cached(&[1.0, 1.0], &mut cache);
+        layer.backward(&mut cache, &[1.0], 0.1);
+
+        assert!((layer.perceptrons[0].weights[0] - 0.4).abs() < 1e-10);
+        assert_eq!(layer.perceptrons[0].weights[1], 0.0);
+    }
+
+    #[test]
+    fn test_accumulate_gradients() {
+        let perceptron = Perceptron::new(vec![1.0], 0.0);
+        let layer = Layer::new(vec![perceptron], Activation::ReLU);
+
+        let mut cache = LayerCache::default();
+        layer.zero_gradients(&mut cache);
+        layer.forward_cached(&[1.0], &mut cache);
+        layer.accumulate_gradients(&mut cache, &[1.0]);
+        layer.forward_cached(&[3.0], &mut cache);
+        layer.accumulate_gradients(&mut cache, &[1.0]);
+
+        // weight gradient 1 + 3, bias gradient 1 + 1
+        assert!((cache.gradient_squared_norm() - 20.0).abs() < 1e-10);
+
+        layer.add_weight_decay(&mut cache, 0.5);
+        assert!((cache.gradient_squared_norm() - (4.5 * 4.5 + 4.0)).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_random_layer_shape() {
+        let layer = Layer::random(3, 4, Activation::ReLU, &mut rand::rng());
+
+        assert_eq!(layer.perceptrons.len(), 4);
+        assert_eq!(layer.input_width(), 3);
+        assert!(layer.perceptrons.iter().flat_map(|p| &p.weights).all(|w| (-0.5..0.5).contains(w)));
+    }
+
+    #[test]
+    fn test_forward_matches_forward_cached() {
+        let p1 = Perceptron::new(vec![0.5, -0.3], 0.1);
+        let p2 = Perceptron::new(vec![-0.2, 0.8], 0.0);
+        let layer = Layer::new(vec![p1, p2], Activation::Sigmoid);
+
+        let mut cache = LayerCache::default();
+        assert_eq!(layer.forward(&[1.0, 2.0]), layer.forward_cached(&[1.0, 2.0], &mut cache));
+    }
+
+    #[test]
+    fn test_frozen_layer_passes_gradients_without_updating() {
+        let perceptron = Perceptron::new(vec![0.5], 0.0);
+        let mut layer = Layer::new(vec![perceptron], Activation::ReLU);
+        layer.trainable = false;
+
+        let mut cache = LayerCache::default();
+        layer.forward_cached(&[2.0], &mut cache);
+        let input_gradients = layer.backward(&mut cache, &[1.0], 0.1);
+
+        assert!((input_gradients[0] - 0.5).abs() < 1e-10);
+        assert_eq!(cache.gradient_squared_norm(), 0.0);
+        assert_eq!(layer.perceptrons[0].weights[0], 0.5);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,363 @@
+use std::collections::VecDeque;
+use std::ops::ControlFlow;
+use crate::ml::model::Model;
+use crate::ml::parameters;
+use crate::ml::training::{self, TrainingContext};
+
+/// Sufficient-decrease constant of the Armijo condition.
+const ARMIJO: f64 = 1e-4;
+
+/// Full-batch limited-memory BFGS over every weight and bias of a model,
+/// treated as one flat vector (see `parameters::flatten`).
+///
+/// The objective is the model's weighted mean loss over the data plus the
+/// L2 penalty `weight_decay / 2 * |w|^2` of each trainable layer, using the
+/// decay of its parameter group. Frozen layers get a zero gradient and stay
+/// put; learning-rate scales do not apply.
+#[derive(Clone, Debug)]
+pub struct Lbfgs {
+    /// Number of recent `(s, y)` pairs kept for the inverse Hessian estimate.
+    pub history_size: usize,
+    pub max_iterations: usize,
+    /// Stop once the largest absolute gradient entry falls below this.
+    pub gradient_tolerance: f64,
+    /// Stop once an iteration lowers the loss by less than this fraction.
+    pub loss_tolerance: f64,
+    /// Halvings of the step tried by the backtracking line search.
+    pub max_line_search_steps: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    GradientTolerance,
+    LossTolerance,
+    MaxIterations,
+    /// No step along the search direction lowered the loss enough.
+    LineSearchFailed,
+    /// The `on_iteration` callback of `minimize_with` asked to stop.
+    Interrupted,
+}
+
+/// State after one L-BFGS iteration.
+#[derive(Clone, Copy, Debug)]
+pub struct LbfgsIteration {
+    pub iteration: usize,
+    pub loss: f64,
+    /// Largest absolute gradient entry.
+    pub gradient_norm: f64,
+    pub step_size: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LbfgsReport {
+    pub iterations: usize,
+    /// Objective evaluations, each one a full pass over the data.
+    pub evaluations: usize,
+    pub loss: f64,
+    pub gradient_norm: f64,
+    pub stop: StopReason,
+}
+
+impl Default for Lbfgs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lbfgs {
+    pub fn new() -> Self {
+        Self {
+            history_size: 10,
+            max_iterations: 100,
+            gradient_tolerance: 1e-5,
+            loss_tolerance: 1e-9,
+            max_line_search_steps: 20,
+        }
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Minimizes the loss of `model` on `data`, updating it in place. Every
+    /// iteration and the model as it stands after it go to `on_iteration`,
+    /// e.g. for progress reporting; returning `Break` stops with
+    /// `StopReason::Interrupted`.
+    pub fn minimize_with(
+        &self,
+        model: &mut Model,
+        data: &[(Vec<f64>, Vec<f64>)],
+        mut on_iteration: impl FnMut(&LbfgsIteration, &Model) -> ControlFlow<()>,
+    ) -> LbfgsReport {
+        let mut objective = Objective::new(data);
+        let mut x = model.parameters();
+        let mut loss = objective.evaluate(model, &x);
+        let mut gradient = objective.gradient.clone();
+        let mut history: VecDeque<Correction> = VecDeque::new();
+
+        let report = |iterations, loss, gradient: &[f64], stop, evaluations| LbfgsReport {
+            iterations,
+            evaluations,
+            loss,
+            gradient_norm: max_abs(gradient),
+            stop,
+        };
+        if max_abs(&gradient) < self.gradient_tolerance {
+            return report(0, loss, &gradient, StopReason::GradientTolerance, objective.evaluations);
+        }
+
+        let mut trial = vec![0.0; x.len()];
+        for iteration in 1..=self.max_iterations {
+            let mut direction = two_loop(&gradient, &history);
+            let mut slope = dot(&direction, &gradient);
+            if slope >= 0.0 {
+                // Not a descent direction: fall back to steepest descent
+                history.clear();
+                direction = gradient.iter().map(|g| -g).collect();
+                slope = -dot(&gradient, &gradient);
+            }
+
+            // Without curvature information, start with a unit-length step
+            let mut step = if history.is_empty() { (1.0 / dot(&gradient, &gradient).sqrt()).min(1.0) } else { 1.0 };
+            let mut accepted = None;
+            for _ in 0..self.max_line_search_steps {
+                for ((trial, x), d) in trial.iter_mut().zip(&x).zip(&direction) {
+                    *trial = x + step * d;
+                }
+                let trial_loss = objective.evaluate(model, &trial);
+                if trial_loss <= loss + ARMIJO * step * slope {
+                    accepted = Some(trial_loss);
+                    break;
+                }
+                step *= 0.5;
+            }
+            let Some(new_loss) = accepted else {
+                parameters::unflatten(&mut model.layers, &x).expect("same length as the model");
+                return report(iteration - 1, loss, &gradient, StopReason::LineSearchFailed, objective.evaluations);
+            };
+
+            let s: Vec<f64> = trial.iter().zip(&x).map(|(new, old)| new - old).collect();
+            let y: Vec<f64> = objective.gradient.iter().zip(&gradient).map(|(new, old)| new - old).collect();
+            let curvature = dot(&s, &y);
+            // Skip pairs that would make the estimate indefinite
+            if curvature > 1e-10 * dot(&y, &y) {
+                if history.len() >= self.history_size.max(1) {
+                    history.pop_front();
+                }
+                history.push_back(Correction { rho: 1.0 / curvature, s, y });
+            }
+
+            let decrease = loss - new_loss;
+            std::mem::swap(&mut x, &mut trial);
+            gradient.copy_from_slice(&objective.gradient);
+            loss = new_loss;
+            let state = LbfgsIteration { iteration, loss, gradient_norm: max_abs(&gradient), step_size: step };
+            if on_iteration(&state, model).is_break() {
+                return report(iteration, loss, &gradient, StopReason::Interrupted, objective.evaluations);
+            }
+
+            if max_abs(&gradient) < self.gradient_tolerance {
+                return report(iteration, loss, &gradient, StopReason::GradientTolerance, objective.evaluations);
+            }
+            if decrease <= self.loss_tolerance * loss.abs().max(1.0) {
+                return report(iteration, loss, &gradient, StopReason::LossTolerance, objective.evaluations);
+            }
+        }
+
+        report(self.max_iterations, loss, &gradient, StopReason::MaxIterations, objective.evaluations)
+    }
+}
+
+/// One `(s, y)` pair: the change in parameters and in gradient over an
+/// iteration, with `rho = 1 / (s . y)`.
+struct Correction {
+    s: Vec<f64>,
+    y: Vec<f64>,
+    rho: f64,
+}
+
+/// Full-batch loss and flat gradient, reusing its buffers between calls.
+struct Objective<'a> {
+    data: &'a [(Vec<f64>, Vec<f64>)],
+    context: TrainingContext,
+    gradient: Vec<f64>,
+    evaluations: usize,
+}
+
+impl<'a> Objective<'a> {
+    fn new(data: &'a [(Vec<f64>, Vec<f64>)]) -> Self {
+        Self { data, context: TrainingContext::new(), gradient: Vec::new(), evaluations: 0 }
+    }
+
+    /// Loads `x` into `model` and returns the objective there, leaving its
+    /// gradient in `self.gradient`.
+    fn evaluate(&mut self, model: &mut Model, x: &[f64]) -> f64 {
+        parameters::unflatten(&mut model.layers, x).expect("same length as the model");
+        self.evaluations += 1;
+
+        let context = &mut self.context;
+        context.zero_gradients(&model.layers);
+        let mut loss = 0.0;
+        for (input, target) in self.data {
+            loss += context.accumulate_sample(&model.layers, input, target, &model.loss, &model.class_weighting);
+        }
+        let count = self.data.len().max(1) as f64;
+        loss /= count;
+
+        for (index, (layer, cache)) in model.layers.iter().zip(&mut context.caches).enumerate() {
+            cache.scale_gradients(1.0 / count);
+            let (_, weight_decay) = training::group_settings(&model.parameter_groups, index, model.weight_decay);
+            if weight_decay > 0.0 && layer.trainable {
+                layer.add_weight_decay(cache, weight_decay);
+                let squared: f64 = layer.perceptrons.iter().flat_map(|p| &p.weights).map(|w| w * w).sum();
+                loss += 0.5 * weight_decay * squared;
+            }
+        }
+
+        parameters::flatten_gradients(&model.layers, &context.caches, &mut self.gradient);
+        loss
+    }
+}
+
+/// L-BFGS two-loop recursion: `-H * gradient` for the inverse Hessian
+/// estimate `H` built from `history`, oldest pair first.
+fn two_loop(gradient: &[f64], history: &VecDeque<Correction>) -> Vec<f64> {
+    let mut q = gradient.to_vec();
+    let mut alphas = Vec::with_capacity(history.len());
+    for correction in history.iter().rev() {
+        let alpha = correction.rho * dot(&correction.s, &q);
+        axpy(-alpha, &correction.y, &mut q);
+        alphas.push(alpha);
+    }
+
+    // Initial Hessian scaled by s.y / y.y of the newest pair
+    if let Some(newest) = history.back() {
+        let gamma = 1.0 / (newest.rho * dot(&newest.y, &newest.y));
+        q.iter_mut().for_each(|value| *value *= gamma);
+    }
+
+    for (correction, alpha) in history.iter().zip(alphas.iter().rev()) {
+        let beta = correction.rho * dot(&correction.y, &q);
+        axpy(alpha - beta, &correction.s, &mut q);
+    }
+    q.iter_mut().for_each(|value| *value = -*value);
+    q
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    crate::ml::simd::dot(a, b)
+}
+
+fn axpy(alpha: f64, x: &[f64], y: &mut [f64]) {
+    crate::ml::simd::axpy(alpha, x, y)
+}
+
+fn max_abs(values: &[f64]) -> f64 {
+    values.iter().fold(0.0, |max, value| value.abs().max(max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::activation::Activation;
+    use crate::ml::layer::Layer;
+    use crate::ml::loss::Loss;
+    use crate::ml::seed::RunSeed;
+
+    fn data() -> Vec<(Vec<f64>, Vec<f64>)> {
+        (0..40)
+            .map(|i| {
+                let x = (i % 8) as f64 / 8.0;
+                let y = (i / 8) as f64 / 5.0;
+                // Overlapping classes so the optimum is finite
+                let label = if x + y + ((i * 7) % 5) as f64 * 0.1 > 0.9 { 1.0 } else { 0.0 };
+                (vec![x, y], vec![label])
+            })
+            .collect()
+    }
+
+    fn model(seed: u64) -> Model {
+        let mut rng = RunSeed(seed).rng("init", 0);
+        Model::new(2, vec![
+            Layer::random(2, 4, Activation::Sigmoid, &mut rng),
+            Layer::random(4, 1, Activation::Sigmoid, &mut rng),
+        ])
+        .unwrap()
+        .with_loss(Loss::BinaryCrossEntropy)
+        .with_weight_decay(1e-3)
+    }
+
+    #[test]
+    fn test_gradient_matches_finite_differences() {
+        let data = data();
+        let mut model = model(1);
+        let mut objective = Objective::new(&data);
+        let x = model.parameters();
+        objective.evaluate(&mut model, &x);
+        let gradient = objective.gradient.clone();
+
+        let h = 1e-6;
+        for i in 0..x.len() {
+            let (mut plus, mut minus) = (x.clone(), x.clone());
+            plus[i] += h;
+            minus[i] -= h;
+            let numeric = (objective.evaluate(&mut model, &plus) - objective.evaluate(&mut model, &minus)) / (2.0 * h);
+            assert!((gradient[i] - numeric).abs() < 1e-6, "parameter {}: {} vs {}", i, gradient[i], numeric);
+        }
+    }
+
+    #[test]
+    fn test_lbfgs_beats_full_batch_gradient_descent() {
+        let data = data();
+        let mut sgd = model(2);
+        let mut context = TrainingContext::new();
+        for _ in 0..50 {
+            sgd.train_batch(&mut context, &data, 0.5);
+        }
+
+        let mut lbfgs = model(2);
+        let mut losses = Vec::new();
+        let report = Lbfgs::new().with_max_iterations(50).minimize_with(&mut lbfgs, &data, |iteration, _| {
+            losses.push(iteration.loss);
+            ControlFlow::Continue(())
+        });
+
+        assert!(losses.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(report.loss < sgd.mean_loss(&data));
+        assert_eq!(report.iterations, losses.len());
+        assert_eq!(report.loss, *losses.last().unwrap());
+    }
+
+    #[test]
+    fn test_interrupt_keeps_current_parameters() {
+        let data = data();
+        let mut model = model(4);
+        let mut seen = Vec::new();
+        let report = Lbfgs::new().minimize_with(&mut model, &data, |iteration, current| {
+            seen.push(current.parameters());
+            if iteration.iteration == 3 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        });
+
+        assert_eq!(report.stop, StopReason::Interrupted);
+        assert_eq!(report.iterations, 3);
+        assert_eq!(seen.len(), 3);
+        assert_eq!(seen.last(), Some(&model.parameters()));
+    }
+
+    #[test]
+    fn test_stops_at_gradient_tolerance() {
+        // A single linear neuron fitting y = 2x - 1 is a convex quadratic
+        let data: Vec<(Vec<f64>, Vec<f64>)> = (0..10).map(|i| (vec![i as f64 / 10.0], vec![0.2 * i as f64 - 1.0])).collect();
+        let mut rng = RunSeed(3).rng("init", 0);
+        let mut model = Model::new(1, vec![Layer::random(1, 1, Activation::Linear, &mut rng)])
+            .unwrap()
+            .with_loss(Loss::MeanSquaredError);
+
+        let lbfgs = Lbfgs { gradient_tolerance: 1e-8, loss_tolerance: 0.0, ..Lbfgs::new() };
+        let report = lbfgs.minimize_with(&mut model, &data, |_, _| ControlFlow::Continue(()));
+        assert_eq!(report.stop, StopReason::GradientTolerance);
+        assert!((model.layers[0].perceptrons[0].weights[0] - 2.0).abs() < 1e-6);
+        assert!((model.layers[0].perceptrons[0].bias + 1.0).abs() < 1e-6);
+    }
+}
@@ -1,17 +1,148 @@
+use crate::ml::metrics;
+
+// Keeps the log terms of the cross-entropy losses finite.
+const EPSILON: f64 = 1e-12;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Loss{
-    SumSquaredError
+    SumSquaredError,
+    MeanSquaredError,
+    MeanAbsoluteError,
+    Huber { delta: f64 },
+    LogCosh,
+    /// Pinball loss for the given quantile in (0, 1).
+    Quantile { quantile: f64 },
+    /// Expects probabilities, e.g. from a sigmoid output.
+    BinaryCrossEntropy,
+    /// Cross-entropy scaled by `(1 - p_t)^gamma` to focus on hard examples,
+    /// with `alpha` weighting the positive class and `1 - alpha` the negative.
+    Focal { gamma: f64, alpha: f64 },
 }
 
 impl Loss{
     pub fn calculate(&self, predicted: f64, actual: f64) -> f64 {
+        let error = predicted - actual;
         match self {
             Loss::SumSquaredError => sum_squared_error(predicted, actual),
+            Loss::MeanSquaredError => error.powi(2),
+            Loss::MeanAbsoluteError => error.abs(),
+            Loss::Huber { delta } => {
+                if error.abs() <= *delta {
+                    0.5 * error.powi(2)
+                } else {
+                    delta * (error.abs() - 0.5 * delta)
+                }
+            }
+            // log(cosh(x)) without overflowing cosh for large errors
+            Loss::LogCosh => error.abs() + (-2.0 * error.abs()).exp().ln_1p() - std::f64::consts::LN_2,
+            Loss::Quantile { quantile } => {
+                if error < 0.0 {
+                    -quantile * error
+                } else {
+                    (1.0 - quantile) * error
+                }
+            }
+            Loss::BinaryCrossEntropy => {
+                let p = predicted.clamp(EPSILON, 1.0 - EPSILON);
+                -(actual * p.ln() + (1.0 - actual) * (1.0 - p).ln())
+            }
+            Loss::Focal { gamma, alpha } => {
+                let p = predicted.clamp(EPSILON, 1.0 - EPSILON);
+                -(actual * alpha * (1.0 - p).powf(*gamma) * p.ln()
+                    + (1.0 - actual) * (1.0 - alpha) * p.powf(*gamma) * (1.0 - p).ln())
+            }
         }
     }
     pub fn derivative(&self, predicted: f64, actual: f64) -> f64 {
+        let error = predicted - actual;
         match self {
             Loss::SumSquaredError => predicted - actual,
+            Loss::MeanSquaredError => 2.0 * error,
+            Loss::MeanAbsoluteError => sign(error),
+            Loss::Huber { delta } => error.clamp(-delta, *delta),
+            Loss::LogCosh => error.tanh(),
+            Loss::Quantile { quantile } => {
+                if error < 0.0 {
+                    -quantile
+                } else {
+                    1.0 - quantile
+                }
+            }
+            Loss::BinaryCrossEntropy => {
+                let p = predicted.clamp(EPSILON, 1.0 - EPSILON);
+                (p - actual) / (p * (1.0 - p))
+            }
+            Loss::Focal { gamma, alpha } => {
+                let p = predicted.clamp(EPSILON, 1.0 - EPSILON);
+                let positive = gamma * (1.0 - p).powf(gamma - 1.0) * p.ln() - (1.0 - p).powf(*gamma) / p;
+                let negative = p.powf(*gamma) / (1.0 - p) - gamma * p.powf(gamma - 1.0) * (1.0 - p).ln();
+                actual * alpha * positive + (1.0 - actual) * (1.0 - alpha) * negative
+            }
+        }
+    }
+
+    /// Loss over a whole output vector: summed for `SumSquaredError`,
+    /// averaged over the outputs for every other loss.
+    pub fn compute(&self, predicted: &[f64], actual: &[f64]) -> f64 {
+        let total: f64 = predicted.iter().zip(actual).map(|(p, a)| self.calculate(*p, *a)).sum();
+        total / self.divisor(predicted.len())
+    }
+
+    fn divisor(&self, outputs: usize) -> f64 {
+        match self {
+            Loss::SumSquaredError => 1.0,
+            _ => outputs.max(1) as f64,
+        }
+    }
+}
+
+/// Per-sample reweighting of a loss by the sample's class, plus label
+/// smoothing. The class of a target is `metrics::predicted_class`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClassWeighting {
+    pub class_weights: Option<Vec<f64>>,
+    /// `cost_matrix[actual][predicted]`. A sample of class `c` is weighted by
+    /// the total cost of misclassifying `c`, the sum of row `c`.
+    pub cost_matrix: Option<Vec<Vec<f64>>>,
+    /// Moves targets towards uniform: `y * (1 - s) + s / classes`.
+    pub label_smoothing: f64,
+}
+
+impl ClassWeighting {
+    pub fn balanced(class_weights: Vec<f64>) -> Self {
+        Self { class_weights: Some(class_weights), ..Self::default() }
+    }
+
+    pub fn sample_weight(&self, target: &[f64]) -> f64 {
+        let class = metrics::predicted_class(target);
+        let mut weight = 1.0;
+        if let Some(class_weights) = &self.class_weights {
+            weight *= class_weights.get(class).copied().unwrap_or(1.0);
+        }
+        if let Some(cost_matrix) = &self.cost_matrix {
+            weight *= cost_matrix.get(class).map_or(1.0, |row| row.iter().sum());
+        }
+        weight
+    }
+
+    fn smooth_value(&self, y: f64, classes: f64) -> f64 {
+        y * (1.0 - self.label_smoothing) + self.label_smoothing / classes
+    }
+
+    /// Weighted loss for one sample, with its output gradients written into
+    /// `gradients` so that the allocation can be reused.
+    pub fn loss_and_gradients_into(&self, loss: &Loss, predicted: &[f64], actual: &[f64], gradients: &mut Vec<f64>) -> f64 {
+        let weight = self.sample_weight(actual);
+        let classes = actual.len().max(2) as f64;
+        let divisor = loss.divisor(predicted.len());
+        let mut total = 0.0;
+        gradients.clear();
+        for (p, a) in predicted.iter().zip(actual) {
+            let smoothed = self.smooth_value(*a, classes);
+            total += loss.calculate(*p, smoothed);
+            gradients.push(loss.derivative(*p, smoothed) / divisor * weight);
         }
+        weight * (total / divisor)
     }
 }
 
@@ -19,6 +150,16 @@ pub fn sum_squared_error(predicted: f64, actual: f64) -> f64 {
     return ((predicted - actual).powi(2)) / 2.;
 }
 
+fn sign(x: f64) -> f64 {
+    if x > 0.0 {
+        1.0
+    } else if x < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -27,4 +168,97 @@ mod tests {
     fn test_sum_squared_error() {
         assert_eq!(sum_squared_error(1.0, 2.0), 0.5);
     }
+
+    #[test]
+    fn test_mean_losses_average_outputs() {
+        let predicted = [1.0, 3.0];
+        let actual = [2.0, 1.0];
+
+        assert!((Loss::SumSquaredError.compute(&predicted, &actual) - 2.5).abs() < 1e-10);
+        assert!((Loss::MeanSquaredError.compute(&predicted, &actual) - 2.5).abs() < 1e-10);
+        assert!((Loss::MeanAbsoluteError.compute(&predicted, &actual) - 1.5).abs() < 1e-10);
+        let mut gradients = Vec::new();
+        ClassWeighting::default().loss_and_gradients_into(&Loss::MeanSquaredError, &predicted, &actual, &mut gradients);
+        assert_eq!(gradients, vec![-1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_huber() {
+        let huber = Loss::Huber { delta: 1.0 };
+
+        assert!((huber.calculate(0.5, 0.0) - 0.125).abs() < 1e-10);
+        assert!((huber.calculate(3.0, 0.0) - 2.5).abs() < 1e-10);
+        assert_eq!(huber.derivative(3.0, 0.0), 1.0);
+        assert_eq!(huber.derivative(-0.5, 0.0), -0.5);
+    }
+
+    #[test]
+    fn test_log_cosh() {
+        assert!((Loss::LogCosh.calculate(0.7, 0.0) - 0.7f64.cosh().ln()).abs() < 1e-10);
+        // Stays finite where cosh would overflow
+        assert!((Loss::LogCosh.calculate(1000.0, 0.0) - (1000.0 - std::f64::consts::LN_2)).abs() < 1e-9);
+        assert!((Loss::LogCosh.derivative(0.7, 0.0) - 0.7f64.tanh()).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_quantile() {
+        let loss = Loss::Quantile { quantile: 0.9 };
+
+        // Under-prediction costs more than over-prediction at the 0.9 quantile
+        assert!((loss.calculate(0.0, 1.0) - 0.9).abs() < 1e-10);
+        assert!((loss.calculate(1.0, 0.0) - 0.1).abs() < 1e-10);
+        assert!((loss.derivative(0.0, 1.0) + 0.9).abs() < 1e-10);
+    }
+
+    fn numeric_derivative(loss: Loss, predicted: f64, actual: f64) -> f64 {
+        let h = 1e-6;
+        (loss.calculate(predicted + h, actual) - loss.calculate(predicted - h, actual)) / (2.0 * h)
+    }
+
+    #[test]
+    fn test_cross_entropy_derivatives() {
+        let losses = [
+            Loss::BinaryCrossEntropy,
+            Loss::Focal { gamma: 2.0, alpha: 0.25 },
+            Loss::Focal { gamma: 0.5, alpha: 0.7 },
+        ];
+        for loss in losses {
+            for (predicted, actual) in [(0.3, 1.0), (0.8, 0.0), (0.6, 0.95)] {
+                let expected = numeric_derivative(loss, predicted, actual);
+                assert!((loss.derivative(predicted, actual) - expected).abs() < 1e-5, "{:?}", loss);
+            }
+        }
+    }
+
+    #[test]
+    fn test_focal_down_weights_easy_examples() {
+        let focal = Loss::Focal { gamma: 2.0, alpha: 0.5 };
+        let bce = Loss::BinaryCrossEntropy;
+
+        let easy = focal.calculate(0.95, 1.0) / bce.calculate(0.95, 1.0);
+        let hard = focal.calculate(0.2, 1.0) / bce.calculate(0.2, 1.0);
+        assert!(easy < hard);
+    }
+
+    #[test]
+    fn test_class_weighting() {
+        // Misclassifying ham (class 0) as spam costs 5, the reverse costs 1
+        let weighting = ClassWeighting {
+            class_weights: Some(vec![0.5, 2.0]),
+            cost_matrix: Some(vec![vec![0.0, 5.0], vec![1.0, 0.0]]),
+            label_smoothing: 0.1,
+        };
+
+        assert!((weighting.sample_weight(&[0.0]) - 2.5).abs() < 1e-10);
+        assert!((weighting.sample_weight(&[1.0]) - 2.0).abs() < 1e-10);
+        assert!((weighting.smooth_value(1.0, 2.0) - 0.95).abs() < 1e-10);
+        assert!((weighting.smooth_value(0.0, 3.0) - 0.1 / 3.0).abs() < 1e-10);
+        assert!((weighting.smooth_value(1.0, 3.0) - (0.9 + 0.1 / 3.0)).abs() < 1e-10);
+
+        let mut gradients = Vec::new();
+        let loss = weighting.loss_and_gradients_into(&Loss::SumSquaredError, &[0.5], &[0.0], &mut gradients);
+        // Target 0.0 is smoothed to 0.05
+        assert!((loss - 2.5 * 0.45 * 0.45 / 2.0).abs() < 1e-10);
+        assert!((gradients[0] - 2.5 * (0.5 - 0.05)).abs() < 1e-10);
+    }
 }
\ No newline at end of file
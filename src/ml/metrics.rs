@@ -0,0 +1,109 @@
+/// The target half of every `(input, target)` pair.
+pub fn targets(data: &[(Vec<f64>, Vec<f64>)]) -> Vec<Vec<f64>> {
+    data.iter().map(|(_, target)| target.clone()).collect()
+}
+
+/// Class predicted by a model output: a 0.5 threshold for a single output,
+/// otherwise the index of the largest value.
+pub fn predicted_class(output: &[f64]) -> usize {
+    if output.len() == 1 {
+        return if output[0] > 0.5 { 1 } else { 0 };
+    }
+    output
+        .iter()
+        .enumerate()
+        .fold((0, f64::NEG_INFINITY), |best, (i, &value)| if value > best.1 { (i, value) } else { best })
+        .0
+}
+
+/// Fraction of outputs whose predicted class matches the target's.
+pub fn accuracy(outputs: &[Vec<f64>], targets: &[Vec<f64>]) -> f64 {
+    if outputs.is_empty() {
+        return 0.0;
+    }
+    let correct = outputs
+        .iter()
+        .zip(targets)
+        .filter(|(output, target)| predicted_class(output) == predicted_class(target))
+        .count();
+    correct as f64 / outputs.len() as f64
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RegressionMetrics {
+    pub rmse: f64,
+    pub mae: f64,
+    pub r2: f64,
+}
+
+/// Regression metrics for each output column.
+pub fn regression_metrics_per_output(outputs: &[Vec<f64>], targets: &[Vec<f64>]) -> Vec<RegressionMetrics> {
+    let width = targets.first().map_or(0, Vec::len);
+    let count = targets.len().max(1) as f64;
+
+    (0..width)
+        .map(|column| {
+            let mean = targets.iter().map(|t| t[column]).sum::<f64>() / count;
+            let mut squared = 0.0;
+            let mut absolute = 0.0;
+            let mut total = 0.0;
+            for (output, target) in outputs.iter().zip(targets) {
+                let error = output[column] - target[column];
+                squared += error * error;
+                absolute += error.abs();
+                total += (target[column] - mean).powi(2);
+            }
+            RegressionMetrics {
+                rmse: (squared / count).sqrt(),
+                mae: absolute / count,
+                r2: if total > 0.0 { 1.0 - squared / total } else { 0.0 },
+            }
+        })
+        .collect()
+}
+
+/// Regression metrics averaged over the output columns.
+pub fn regression_metrics(outputs: &[Vec<f64>], targets: &[Vec<f64>]) -> RegressionMetrics {
+    let per_output = regression_metrics_per_output(outputs, targets);
+    let count = per_output.len().max(1) as f64;
+    RegressionMetrics {
+        rmse: per_output.iter().map(|m| m.rmse).sum::<f64>() / count,
+        mae: per_output.iter().map(|m| m.mae).sum::<f64>() / count,
+        r2: per_output.iter().map(|m| m.r2).sum::<f64>() / count,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_predicted_class() {
+        assert_eq!(predicted_class(&[0.7]), 1);
+        assert_eq!(predicted_class(&[0.2]), 0);
+        assert_eq!(predicted_class(&[0.1, 0.7, 0.2]), 1);
+    }
+
+    #[test]
+    fn test_accuracy() {
+        let outputs = vec![vec![0.9], vec![0.4], vec![0.6], vec![0.1]];
+        let targets = vec![vec![1.0], vec![1.0], vec![1.0], vec![0.0]];
+        assert!((accuracy(&outputs, &targets) - 0.75).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_regression_metrics() {
+        let outputs = vec![vec![1.0, 0.0], vec![2.0, 0.0], vec![4.0, 0.0]];
+        let targets = vec![vec![1.0, 1.0], vec![3.0, 1.0], vec![5.0, 1.0]];
+        let per_output = regression_metrics_per_output(&outputs, &targets);
+
+        // Column 0: errors [0, -1, -1], mean target 3, total sum of squares 8
+        assert!((per_output[0].rmse - (2.0f64 / 3.0).sqrt()).abs() < 1e-10);
+        assert!((per_output[0].mae - 2.0 / 3.0).abs() < 1e-10);
+        assert!((per_output[0].r2 - 0.75).abs() < 1e-10);
+        assert!((per_output[1].mae - 1.0).abs() < 1e-10);
+
+        let mean = regression_metrics(&outputs, &targets);
+        assert!((mean.mae - 5.0 / 6.0).abs() < 1e-10);
+    }
+}
\ No newline at end of file
@@ -2,4 +2,27 @@ pub mod perceptron;
 pub mod activation;
 pub mod layer;
 pub mod model;
-pub mod loss;
\ No newline at end of file
+pub mod seed;
+pub mod simd;
+pub mod persistence;
+pub mod checkpoint;
+pub mod history;
+pub mod summary;
+pub mod autoencoder;
+pub mod baselines;
+pub mod training;
+pub mod parameters;
+pub mod lbfgs;
+pub mod evolution;
+pub mod loss;
+pub mod clipping;
+pub mod guard;
+pub mod error;
+pub mod metrics;
+pub mod pruning;
+pub mod quantization;
+pub mod search;
+pub mod cross_validation;
+pub mod predictor;
+pub mod ensemble;
+pub mod attribution;
\ No newline at end of file
@@ -1,58 +1,436 @@
+use rand::Rng;
+use crate::ml::activation::Activation;
 use crate::ml::layer::Layer;
-use crate::ml::loss::Loss;
+use crate::ml::loss::{ClassWeighting, Loss};
+use crate::ml::clipping::GradientClipping;
+use crate::ml::guard::{self, DivergenceError};
+use crate::ml::error::ModelError;
+use crate::ml::predictor::Predictor;
+use crate::ml::training::{self, ParameterGroup, TrainingContext};
+use crate::ml::summary::ModelSummary;
+use crate::ml::parameters;
 
 #[derive(Clone)]
 pub struct Model {
     pub layers: Vec<Layer>,
+    input_width: usize,
+    pub loss: Loss,
+    pub class_weighting: ClassWeighting,
+    /// L2 penalty on the weights, added to their gradients each step.
+    pub weight_decay: f64,
+    pub clipping: GradientClipping,
+    /// When set, `try_train_epoch_with` stops at the first NaN or infinity.
+    pub check_numerics: bool,
+    /// Per-layer learning-rate scales and weight decay. Layers outside every
+    /// group use the plain learning rate and `weight_decay`.
+    pub parameter_groups: Vec<ParameterGroup>,
+}
+
+/// Result of a single training step.
+#[derive(Clone, Copy, Debug)]
+pub struct TrainStep {
+    pub loss: f64,
+    /// Global gradient norm before clipping.
+    pub gradient_norm: f64,
 }
 
 impl Model {
-    pub fn new(layers: Vec<Layer>) -> Self {
-        Self { layers }
+    /// Builds a model taking `input_width` features, checking that every
+    /// layer is consistent and connects to the one before it.
+    pub fn new(input_width: usize, layers: Vec<Layer>) -> Result<Self, ModelError> {
+        validate_layers(input_width, &layers)?;
+        Ok(Self {
+            layers,
+            input_width,
+            loss: Loss::SumSquaredError,
+            class_weighting: ClassWeighting::default(),
+            weight_decay: 0.0,
+            clipping: GradientClipping::none(),
+            check_numerics: false,
+            parameter_groups: Vec::new(),
+        })
+    }
+
+    pub fn input_width(&self) -> usize {
+        self.input_width
+    }
+
+    /// Per-layer shapes, parameter counts and weight statistics.
+    pub fn summary(&self) -> ModelSummary {
+        ModelSummary::of(self)
+    }
+
+    pub fn with_loss(mut self, loss: Loss) -> Self {
+        self.loss = loss;
+        self
+    }
+
+    pub fn with_class_weighting(mut self, class_weighting: ClassWeighting) -> Self {
+        self.class_weighting = class_weighting;
+        self
+    }
+
+    pub fn with_weight_decay(mut self, weight_decay: f64) -> Self {
+        self.weight_decay = weight_decay;
+        self
+    }
+
+    pub fn with_gradient_clipping(mut self, clipping: GradientClipping) -> Self {
+        self.clipping = clipping;
+        self
     }
 
-    pub fn forward(&mut self, input: &[f64]) -> Vec<f64> {
-        let mut current = input.to_vec();
-        for layer in &mut self.layers {
-            current = layer.forward(&current);
+    pub fn with_numeric_checks(mut self) -> Self {
+        self.check_numerics = true;
+        self
+    }
+
+    pub fn with_parameter_groups(mut self, parameter_groups: Vec<ParameterGroup>) -> Self {
+        self.parameter_groups = parameter_groups;
+        self
+    }
+
+    /// Width of the model output.
+    pub fn output_width(&self) -> usize {
+        self.layers.last().map_or(self.input_width, |layer| layer.perceptrons.len())
+    }
+
+    /// Every weight and bias as one flat vector, see `parameters::flatten`.
+    pub fn parameters(&self) -> Vec<f64> {
+        parameters::flatten(&self.layers)
+    }
+
+    pub fn set_parameters(&mut self, parameters: &[f64]) -> Result<(), ModelError> {
+        parameters::unflatten(&mut self.layers, parameters)
+    }
+
+    /// Keeps only the first `num_layers` layers, e.g. to reuse them as a
+    /// feature extractor. At least one layer has to stay.
+    pub fn truncate(&mut self, num_layers: usize) -> Result<(), ModelError> {
+        self.replace_layers(num_layers, Vec::new())
+    }
+
+    /// Adds `layer` on top of the model, checking that it takes the current
+    /// output width.
+    pub fn append_layer(&mut self, layer: Layer) -> Result<(), ModelError> {
+        self.replace_layers(self.layers.len(), vec![layer])
+    }
+
+    /// Appends freshly initialized layers with the given widths and
+    /// activations. Fails without changing the model if a width is 0.
+    pub fn append_dense(&mut self, layers: &[(usize, Activation)], rng: &mut impl Rng) -> Result<(), ModelError> {
+        self.replace_head(self.layers.len(), layers, rng)
+    }
+
+    /// Drops every layer from `from_layer` on and appends fresh ones in
+    /// their place, e.g. a new output head for a different task.
+    pub fn replace_head(
+        &mut self,
+        from_layer: usize,
+        layers: &[(usize, Activation)],
+        rng: &mut impl Rng,
+    ) -> Result<(), ModelError> {
+        if from_layer > self.layers.len() {
+            return Err(ModelError::LayerIndex { layer: from_layer, layers: self.layers.len() });
         }
-        current
+        let mut width = from_layer.checked_sub(1).map_or(self.input_width, |last| self.layers[last].perceptrons.len());
+        let mut fresh = Vec::new();
+        for &(neurons, activation) in layers {
+            fresh.push(Layer::random(width, neurons, activation, rng));
+            width = neurons;
+        }
+        self.replace_layers(from_layer, fresh)
     }
 
-    pub fn train(&mut self, input: &[f64], target: &[f64], learning_rate: f64) -> f64 {
+    /// Draws new random parameters for `layer`, keeping its shape,
+    /// activation, pruning mask and trainable flag.
+    pub fn reinitialize(&mut self, layer: usize, rng: &mut impl Rng) -> Result<(), ModelError> {
+        let layers = self.layers.len();
+        self.layers.get_mut(layer).ok_or(ModelError::LayerIndex { layer, layers })?.reinitialize(rng);
+        Ok(())
+    }
 
-        let output = self.forward(input);
+    /// Keeps the first `keep` layers followed by `fresh`, or leaves the model
+    /// unchanged if the result would be empty or fail the checks of
+    /// `Model::new`.
+    fn replace_layers(&mut self, keep: usize, fresh: Vec<Layer>) -> Result<(), ModelError> {
+        let keep = keep.min(self.layers.len());
+        if keep + fresh.len() == 0 {
+            return Err(ModelError::NoLayers);
+        }
+        let old_head = self.layers.split_off(keep);
+        self.layers.extend(fresh);
+        let result = validate_layers(self.input_width, &self.layers);
+        if result.is_err() {
+            self.layers.truncate(keep);
+            self.layers.extend(old_head);
+        }
+        result
+    }
 
-        let mut loss = 0.0;
-        for i in 0..output.len() {
-            loss += Loss::SumSquaredError.calculate(output[i], target[i]);
+    /// Keeps the parameters of `layer` fixed during training.
+    pub fn freeze(&mut self, layer: usize) {
+        self.layers[layer].trainable = false;
+    }
+
+    pub fn unfreeze(&mut self, layer: usize) {
+        self.layers[layer].trainable = true;
+    }
+
+    /// Freezes every layer except the last `trainable` ones, e.g. to train
+    /// only a new head on top of pretrained layers.
+    pub fn freeze_all_but_last(&mut self, trainable: usize) {
+        let frozen = self.layers.len().saturating_sub(trainable);
+        for (index, layer) in self.layers.iter_mut().enumerate() {
+            layer.trainable = index >= frozen;
         }
+    }
 
-        let mut gradients = Vec::new();
-        for i in 0..output.len() {
-            gradients.push(Loss::SumSquaredError.derivative(output[i], target[i]));
+    pub fn forward(&self, input: &[f64]) -> Vec<f64> {
+        match self.layers.split_first() {
+            Some((first, rest)) => rest.iter().fold(first.forward(input), |current, layer| layer.forward(&current)),
+            None => input.to_vec(),
         }
+    }
+
+    /// Like `forward`, but runs in the buffers of `context` instead of
+    /// allocating, e.g. to evaluate many samples with one context.
+    pub fn forward_with<'a>(&self, context: &'a mut TrainingContext, input: &'a [f64]) -> &'a [f64] {
+        context.forward(&self.layers, input)
+    }
+
+    /// Mean of `loss` over `data`, without updating the model.
+    pub fn mean_loss(&self, data: &[(Vec<f64>, Vec<f64>)]) -> f64 {
+        let mut context = TrainingContext::new();
+        let total: f64 =
+            data.iter().map(|(input, target)| self.loss.compute(self.forward_with(&mut context, input), target)).sum();
+        total / data.len().max(1) as f64
+    }
 
-        for layer in self.layers.iter_mut().rev() {
-            gradients = layer.backward(&gradients, learning_rate);
+    /// Runs `forward` on every input of `data`.
+    pub fn forward_all(&self, data: &[(Vec<f64>, Vec<f64>)]) -> Vec<Vec<f64>> {
+        data.iter().map(|(input, _)| self.forward(input)).collect()
+    }
+
+    /// Gradient of output `output_index` with respect to each input,
+    /// backpropagated without touching the weights.
+    pub fn input_gradients(&self, input: &[f64], output_index: usize) -> Vec<f64> {
+        let mut context = TrainingContext::new();
+        let output = context.forward(&self.layers, input);
+        let mut gradients = vec![0.0; output.len()];
+        gradients[output_index] = 1.0;
+
+        context.zero_gradients(&self.layers);
+        context.accumulate_gradients(&self.layers, &gradients).to_vec()
+    }
+
+    /// Like `forward`, but reports a dimension mismatch instead of panicking.
+    pub fn try_forward(&self, input: &[f64]) -> Result<Vec<f64>, ModelError> {
+        self.check_input(input)?;
+        Ok(self.forward(input))
+    }
+
+    pub fn train(&mut self, context: &mut TrainingContext, input: &[f64], target: &[f64], learning_rate: f64) -> f64 {
+        self.train_step(context, input, target, learning_rate).loss
+    }
+
+    /// Walks the layer widths and returns the width of the model output.
+    fn check_input(&self, input: &[f64]) -> Result<usize, ModelError> {
+        let mut width = input.len();
+        for (index, layer) in self.layers.iter().enumerate() {
+            if let Some((neuron, expected)) = layer.input_mismatch(width) {
+                return Err(ModelError::InputSize { layer: index, neuron, expected, found: width });
+            }
+            width = layer.perceptrons.len();
+        }
+        Ok(width)
+    }
+
+    fn check_sample(&self, input: &[f64], target: &[f64]) -> Result<(), ModelError> {
+        let width = self.check_input(input)?;
+        if target.len() != width {
+            return Err(ModelError::TargetSize { expected: width, found: target.len() });
+        }
+        Ok(())
+    }
+
+    /// One update from a single sample. `context` holds the caches and
+    /// gradients of the step and can be reused across steps and models.
+    pub fn train_step(
+        &mut self,
+        context: &mut TrainingContext,
+        input: &[f64],
+        target: &[f64],
+        learning_rate: f64,
+    ) -> TrainStep {
+        self.step(context, std::iter::once((input, target)), learning_rate)
+    }
+
+    /// One update from the averaged gradients of every sample in `batch`.
+    pub fn train_batch(
+        &mut self,
+        context: &mut TrainingContext,
+        batch: &[(Vec<f64>, Vec<f64>)],
+        learning_rate: f64,
+    ) -> TrainStep {
+        self.step(context, batch.iter().map(|(input, target)| (input.as_slice(), target.as_slice())), learning_rate)
+    }
+
+    fn step<'a>(
+        &mut self,
+        context: &mut TrainingContext,
+        samples: impl Iterator<Item = (&'a [f64], &'a [f64])>,
+        learning_rate: f64,
+    ) -> TrainStep {
+        let step = self.compute_step(context, samples);
+        self.apply_step(context, learning_rate);
+        step
+    }
+
+    /// Averaged, decayed and clipped gradients of `samples`, left in
+    /// `context` without updating the parameters.
+    fn compute_step<'a>(
+        &self,
+        context: &mut TrainingContext,
+        samples: impl Iterator<Item = (&'a [f64], &'a [f64])>,
+    ) -> TrainStep {
+        context.zero_gradients(&self.layers);
+
+        let mut loss = 0.0;
+        let mut count = 0;
+        for (input, target) in samples {
+            loss += context.accumulate_sample(&self.layers, input, target, &self.loss, &self.class_weighting);
+            count += 1;
+        }
+
+        for (index, (layer, cache)) in self.layers.iter().zip(&mut context.caches).enumerate() {
+            if count > 1 {
+                cache.scale_gradients(1.0 / count as f64);
+            }
+            let (_, weight_decay) = training::group_settings(&self.parameter_groups, index, self.weight_decay);
+            if weight_decay > 0.0 {
+                layer.add_weight_decay(cache, weight_decay);
+            }
         }
 
-        loss
+        let gradient_norm = self.clipping.apply(&mut context.caches);
+
+        TrainStep { loss: loss / count.max(1) as f64, gradient_norm }
+    }
+
+    fn apply_step(&mut self, context: &TrainingContext, learning_rate: f64) {
+        let groups = &self.parameter_groups;
+        context.apply_gradients(&mut self.layers, |index| layer_learning_rate(groups, index, learning_rate));
     }
 
     pub fn train_epoch(
         &mut self,
+        context: &mut TrainingContext,
         data: &[(Vec<f64>, Vec<f64>)],
         learning_rate: f64,
     ) -> f64 {
         let mut total_loss = 0.0;
 
         for (input, target) in data {
-            total_loss += self.train(input, target, learning_rate);
+            total_loss += self.train(context, input, target, learning_rate);
+        }
+
+        total_loss / data.len() as f64
+    }
+
+    /// One pass over `data` in mini-batches of `batch_size` samples.
+    pub fn train_epoch_batched(
+        &mut self,
+        context: &mut TrainingContext,
+        data: &[(Vec<f64>, Vec<f64>)],
+        learning_rate: f64,
+        batch_size: usize,
+    ) -> f64 {
+        let mut total_loss = 0.0;
+
+        for batch in data.chunks(batch_size.max(1)) {
+            total_loss += self.train_batch(context, batch, learning_rate).loss * batch.len() as f64;
         }
 
         total_loss / data.len() as f64
     }
+
+    /// Like `train_epoch`, but checks every sample's dimensions and, with
+    /// `check_numerics` enabled, stops at the first non-finite activation,
+    /// gradient or parameter. The failing step is not applied. Each step's
+    /// index and result go to `on_step`, e.g. to record per-batch history.
+    pub fn try_train_epoch_with(
+        &mut self,
+        context: &mut TrainingContext,
+        data: &[(Vec<f64>, Vec<f64>)],
+        learning_rate: f64,
+        epoch: usize,
+        mut on_step: impl FnMut(usize, &TrainStep),
+    ) -> Result<f64, ModelError> {
+        let mut total_loss = 0.0;
+
+        for (sample, (input, target)) in data.iter().enumerate() {
+            self.check_sample(input, target)?;
+
+            if !self.check_numerics {
+                let step = self.train_step(context, input, target, learning_rate);
+                on_step(sample, &step);
+                total_loss += step.loss;
+                continue;
+            }
+
+            // Check the step before applying it, so that a diverging model
+            // keeps its last finite parameters
+            let step = self.compute_step(context, std::iter::once((input.as_slice(), target.as_slice())));
+            on_step(sample, &step);
+            total_loss += step.loss;
+
+            let groups = &self.parameter_groups;
+            let update = guard::find_non_finite_update(self, context, |index| {
+                layer_learning_rate(groups, index, learning_rate)
+            });
+            if let Some(location) = update {
+                return Err(DivergenceError {
+                    location,
+                    epoch,
+                    sample,
+                    last_finite: Box::new(self.clone()),
+                }.into());
+            }
+            self.apply_step(context, learning_rate);
+        }
+
+        Ok(total_loss / data.len() as f64)
+    }
+}
+
+impl Predictor for Model {
+    fn predict(&self, input: &[f64]) -> Vec<f64> {
+        self.forward(input)
+    }
+}
+
+/// Learning rate of layer `index`, scaled by its parameter group.
+fn layer_learning_rate(groups: &[ParameterGroup], index: usize, learning_rate: f64) -> f64 {
+    learning_rate * training::group_settings(groups, index, 0.0).0
+}
+
+fn validate_layers(input_width: usize, layers: &[Layer]) -> Result<(), ModelError> {
+    let mut width = input_width;
+    for (index, layer) in layers.iter().enumerate() {
+        if layer.perceptrons.is_empty() {
+            return Err(ModelError::EmptyLayer { layer: index });
+        }
+        let expected = layer.input_width();
+        if let Some((neuron, found)) = layer.input_mismatch(expected) {
+            return Err(ModelError::InconsistentLayer { layer: index, neuron, expected, found });
+        }
+        if expected != width {
+            return Err(ModelError::LayerMismatch { layer: index, expected, found: width });
+        }
+        width = layer.perceptrons.len();
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -60,6 +438,7 @@ mod tests {
     use super::*;
     use crate::ml::perceptron::Perceptron;
     use crate::ml::activation::Activation;
+    use crate::ml::seed::RunSeed;
 
     #[test]
     fn test_model_forward() {
@@ -73,7 +452,7 @@ mod tests {
             Perceptron::new(vec![0.1, 0.2, 0.3], 0.0),
         ], Activation::ReLU);
 
-        let mut model = Model::new(vec![hidden_layer, output_layer]);
+        let model = Model::new(2, vec![hidden_layer, output_layer]).unwrap();
         let input = vec![1.0, 2.0];
 
         let output = model.forward(&input);
@@ -99,7 +478,7 @@ mod tests {
             Perceptron::new(vec![1.0, 1.0, 1.0], 0.0),
         ], Activation::ReLU);
 
-        let mut model = Model::new(vec![layer_1, layer_2, layer_3]);
+        let model = Model::new(2, vec![layer_1, layer_2, layer_3]).unwrap();
         let input = vec![4.0, 2.0];
 
         let output = model.forward(&input);
@@ -113,17 +492,18 @@ mod tests {
             Perceptron::new(vec![0.5], 0.0),
         ], Activation::Sigmoid);
 
-        let mut model = Model::new(vec![layer]);
+        let mut model = Model::new(1, vec![layer]).unwrap();
 
         let input = vec![1.0];
         let target = vec![1.0];
         let learning_rate = 0.5;
 
-        let loss_before = model.train(&input, &target, learning_rate);
+        let mut context = TrainingContext::new();
+        let loss_before = model.train(&mut context, &input, &target, learning_rate);
         
         // Train a few more times
         for _ in 0..100 {
-            model.train(&input, &target, learning_rate);
+            model.train(&mut context, &input, &target, learning_rate);
         }
 
         let output = model.forward(&input);
@@ -148,7 +528,7 @@ mod tests {
             Perceptron::new(vec![0.5, -0.5], 0.0),
         ], Activation::Sigmoid);
 
-        let mut model = Model::new(vec![hidden, output]);
+        let mut model = Model::new(2, vec![hidden, output]).unwrap();
 
         let xor_data = vec![
             (vec![0.0, 0.0], vec![0.0]),
@@ -159,8 +539,9 @@ mod tests {
 
         // Train
         let learning_rate = 1.0;
+        let mut context = TrainingContext::new();
         for epoch in 0..1000 {
-            let loss = model.train_epoch(&xor_data, learning_rate);
+            let loss = model.train_epoch(&mut context, &xor_data, learning_rate);
             
             if epoch % 200 == 0 {
                 println!("Epoch {}: loss = {:.4}", epoch, loss);
@@ -178,4 +559,304 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_train_step_reports_pre_clip_norm() {
+        let layer = Layer::new(vec![
+            Perceptron::new(vec![1.0, 1.0], 0.0),
+        ], Activation::ReLU);
+
+        let mut clipped = Model::new(2, vec![layer.clone()]).unwrap()
+            .with_gradient_clipping(GradientClipping::by_norm(0.1));
+        let mut unclipped = Model::new(2, vec![layer]).unwrap();
+
+        // output = 7, loss gradient = 7 -> weight gradients [21, 28], bias gradient 7
+        let input = vec![3.0, 4.0];
+        let target = vec![0.0];
+        let mut context = TrainingContext::new();
+        let clipped_step = clipped.train_step(&mut context, &input, &target, 0.01);
+        let unclipped_step = unclipped.train_step(&mut context, &input, &target, 0.01);
+
+        assert!((clipped_step.gradient_norm - unclipped_step.gradient_norm).abs() < 1e-10);
+        assert!((clipped_step.gradient_norm - 1274f64.sqrt()).abs() < 1e-10);
+
+        // The clipped update moves each weight by at most learning_rate * max_norm
+        let moved = (1.0 - clipped.layers[0].perceptrons[0].weights[1]).abs();
+        assert!(moved <= 0.01 * 0.1 + 1e-12);
+        assert!(unclipped.layers[0].perceptrons[0].weights[1] < 1.0 - 0.2);
+    }
+
+    #[test]
+    fn test_try_train_epoch_reports_divergence() {
+        use crate::ml::guard::NumericStage;
+
+        let hidden = Layer::new(vec![
+            Perceptron::new(vec![1.0], 0.0),
+        ], Activation::ReLU);
+        let output = Layer::new(vec![
+            Perceptron::new(vec![1.0], 0.0),
+        ], Activation::ReLU);
+        let mut model = Model::new(1, vec![hidden, output]).unwrap().with_numeric_checks();
+
+        let data = vec![
+            (vec![1.0], vec![1.0]),
+            (vec![1e200], vec![0.0]),
+        ];
+
+        let mut context = TrainingContext::new();
+        // The second sample overflows the gradient and the update
+        let error = match model.try_train_epoch_with(&mut context, &data, 1.0, 3, |_, _| {}) {
+            Err(ModelError::Diverged(error)) => error,
+            _ => panic!("expected divergence"),
+        };
+        assert_eq!(error.epoch, 3);
+        assert_eq!(error.sample, 1);
+        assert_ne!(error.location.stage, NumericStage::Activation);
+        assert!(error.last_finite.parameters().iter().all(|value| value.is_finite()));
+        // The failing step was rejected before it touched the parameters
+        assert_eq!(model.parameters(), error.last_finite.parameters());
+        assert!(error.to_string().contains("sample 1"));
+    }
+
+    #[test]
+    fn test_try_train_epoch_without_checks() {
+        let layer = Layer::new(vec![
+            Perceptron::new(vec![0.5], 0.0),
+        ], Activation::Sigmoid);
+        let mut model = Model::new(1, vec![layer]).unwrap();
+
+        let data = vec![(vec![1.0], vec![1.0])];
+        let mut context = TrainingContext::new();
+        assert!(model.try_train_epoch_with(&mut context, &data, 0.5, 0, |_, _| {}).is_ok());
+    }
+
+    #[test]
+    fn test_checked_variants_report_mismatch() {
+        let hidden = Layer::new(vec![
+            Perceptron::new(vec![0.2, 0.3], 0.1),
+            Perceptron::new(vec![0.4, 0.5], 0.2),
+        ], Activation::ReLU);
+        let output = Layer::new(vec![
+            Perceptron::new(vec![0.1, 0.2], 0.0),
+        ], Activation::Sigmoid);
+        let mut model = Model::new(2, vec![hidden, output]).unwrap();
+
+        assert!(matches!(
+            model.try_forward(&[1.0, 2.0, 3.0]),
+            Err(ModelError::InputSize { layer: 0, neuron: 0, expected: 2, found: 3 })
+        ));
+        let mut context = TrainingContext::new();
+        let data = vec![(vec![1.0, 2.0], vec![1.0, 0.0])];
+        assert!(matches!(
+            model.try_train_epoch_with(&mut context, &data, 0.1, 0, |_, _| {}),
+            Err(ModelError::TargetSize { expected: 1, found: 2 })
+        ));
+        let data = vec![(vec![1.0, 2.0], vec![1.0])];
+        assert!(model.try_train_epoch_with(&mut context, &data, 0.1, 0, |_, _| {}).is_ok());
+    }
+
+    #[test]
+    fn test_new_rejects_mismatched_layers() {
+        let hidden = Layer::new(vec![
+            Perceptron::new(vec![0.2, 0.3], 0.1),
+            Perceptron::new(vec![0.4, 0.5], 0.2),
+        ], Activation::ReLU);
+        let output = Layer::new(vec![
+            Perceptron::new(vec![0.1, 0.2, 0.3], 0.0),
+        ], Activation::Sigmoid);
+
+        let error = Model::new(2, vec![hidden.clone(), output]).err().unwrap();
+        assert!(matches!(error, ModelError::LayerMismatch { layer: 1, expected: 3, found: 2 }));
+        assert_eq!(error.to_string(), "layer 1 expects 3 inputs, but layer 0 outputs 2");
+
+        let error = Model::new(3, vec![hidden]).err().unwrap();
+        assert!(matches!(error, ModelError::LayerMismatch { layer: 0, expected: 2, found: 3 }));
+    }
+
+    #[test]
+    fn test_new_rejects_inconsistent_layer() {
+        let ragged = Layer::new(vec![
+            Perceptron::new(vec![0.2, 0.3], 0.1),
+            Perceptron::new(vec![0.4], 0.2),
+        ], Activation::ReLU);
+        let empty = Layer::new(Vec::new(), Activation::ReLU);
+
+        assert!(matches!(
+            Model::new(2, vec![ragged]),
+            Err(ModelError::InconsistentLayer { layer: 0, neuron: 1, expected: 2, found: 1 })
+        ));
+        assert!(matches!(Model::new(2, vec![empty]), Err(ModelError::EmptyLayer { layer: 0 })));
+    }
+
+    #[test]
+    fn test_fit_multi_output_regression() {
+        // y = (2x + 1, -x) with a linear output layer
+        let layer = Layer::new(vec![
+            Perceptron::new(vec![0.1], 0.0),
+            Perceptron::new(vec![0.1], 0.0),
+        ], Activation::Linear);
+        let mut model = Model::new(1, vec![layer]).unwrap().with_loss(Loss::MeanSquaredError);
+
+        let data: Vec<(Vec<f64>, Vec<f64>)> = (0..10)
+            .map(|i| {
+                let x = i as f64 / 10.0;
+                (vec![x], vec![2.0 * x + 1.0, -x])
+            })
+            .collect();
+
+        let mut context = TrainingContext::new();
+        for _ in 0..500 {
+            model.train_epoch(&mut context, &data, 0.1);
+        }
+
+        let outputs = model.forward_all(&data);
+        let targets: Vec<Vec<f64>> = data.iter().map(|(_, target)| target.clone()).collect();
+        let metrics = crate::ml::metrics::regression_metrics(&outputs, &targets);
+        assert!(metrics.rmse < 0.01);
+        assert!(metrics.r2 > 0.999);
+    }
+
+    #[test]
+    fn test_train_batch_averages_gradients() {
+        let layer = Layer::new(vec![
+            Perceptron::new(vec![1.0], 0.0),
+        ], Activation::Linear);
+        let mut batched = Model::new(1, vec![layer.clone()]).unwrap();
+        let mut single = Model::new(1, vec![layer]).unwrap();
+
+        // Loss gradients 1.0 and 3.0 average to a weight gradient of
+        // (1 * 1 + 3 * 1) / 2 = 2
+        let batch = vec![(vec![1.0], vec![0.0]), (vec![1.0], vec![-2.0])];
+        let mut context = TrainingContext::new();
+        let step = batched.train_batch(&mut context, &batch, 0.1);
+        assert!((batched.layers[0].perceptrons[0].weights[0] - 0.8).abs() < 1e-10);
+        assert!((step.loss - (0.5 + 4.5) / 2.0).abs() < 1e-10);
+
+        single.train_step(&mut context, &[1.0], &[0.0], 0.1);
+        assert!((single.layers[0].perceptrons[0].weights[0] - 0.9).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_weight_decay_shrinks_weights() {
+        let layer = Layer::new(vec![
+            Perceptron::new(vec![2.0], 0.0),
+        ], Activation::Linear);
+        let mut model = Model::new(1, vec![layer]).unwrap().with_weight_decay(0.5);
+
+        let mut context = TrainingContext::new();
+        // Zero loss gradient: only the decay term 0.5 * 2.0 moves the weight
+        model.train_step(&mut context, &[1.0], &[2.0], 0.1);
+        assert!((model.layers[0].perceptrons[0].weights[0] - 1.9).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_input_gradients() {
+        let hidden = Layer::new(vec![
+            Perceptron::new(vec![2.0, -1.0], 0.0),
+            Perceptron::new(vec![0.5, 3.0], 0.0),
+        ], Activation::ReLU);
+        let output = Layer::new(vec![
+            Perceptron::new(vec![1.0, 2.0], 0.0),
+        ], Activation::Linear);
+        let model = Model::new(2, vec![hidden, output]).unwrap();
+
+        // Both hidden units active: d out / d x = 1 * [2, -1] + 2 * [0.5, 3]
+        let gradients = model.input_gradients(&[1.0, 1.0], 0);
+        assert!((gradients[0] - 3.0).abs() < 1e-10);
+        assert!((gradients[1] - 5.0).abs() < 1e-10);
+        assert_eq!(model.layers[0].perceptrons[0].weights, vec![2.0, -1.0]);
+    }
+
+    #[test]
+    fn test_shared_model_predicts_across_threads() {
+        use std::sync::Arc;
+
+        let layer = Layer::new(vec![
+            Perceptron::new(vec![0.5, -0.5], 0.1),
+        ], Activation::Sigmoid);
+        let model = Arc::new(Model::new(2, vec![layer]).unwrap());
+        let expected = model.predict(&[1.0, 2.0]);
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let model = Arc::clone(&model);
+                std::thread::spawn(move || model.predict(&[1.0, 2.0]))
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_frozen_layer_and_parameter_groups() {
+        let layer = |weight| Layer::new(vec![
+            Perceptron::new(vec![weight], 0.0),
+        ], Activation::Linear);
+        let mut model = Model::new(1, vec![layer(1.0), layer(2.0), layer(1.0)]).unwrap()
+            .with_parameter_groups(vec![ParameterGroup::new(0..1, 0.5, 0.0)]);
+        model.freeze(1);
+
+        let mut context = TrainingContext::new();
+        // output = 2, loss gradient = 2. Layer 0 weight gradient = 2 * 1 * 2 * 1 = 4,
+        // scaled by 0.5; layer 2 weight gradient = 2 * 2 = 4.
+        model.train_step(&mut context, &[1.0], &[0.0], 0.1);
+        assert!((model.layers[0].perceptrons[0].weights[0] - 0.8).abs() < 1e-10);
+        assert_eq!(model.layers[1].perceptrons[0].weights[0], 2.0);
+        assert!((model.layers[2].perceptrons[0].weights[0] - 0.6).abs() < 1e-10);
+
+        model.freeze_all_but_last(1);
+        assert_eq!(model.layers.iter().map(|l| l.trainable).collect::<Vec<_>>(), vec![false, false, true]);
+    }
+
+    #[test]
+    fn test_swap_head_on_pretrained_layers() {
+        let mut rng = rand::rng();
+        let hidden = Layer::random(4, 3, Activation::ReLU, &mut rng);
+        let output = Layer::random(3, 1, Activation::Sigmoid, &mut rng);
+        let mut model = Model::new(4, vec![hidden, output]).unwrap();
+        let pretrained = model.layers[0].perceptrons[0].weights.clone();
+
+        model.replace_head(1, &[(5, Activation::ReLU), (2, Activation::Sigmoid)], &mut rng).unwrap();
+        assert_eq!(model.layers.len(), 3);
+        assert_eq!(model.output_width(), 2);
+        assert_eq!(model.forward(&[0.1, 0.2, 0.3, 0.4]).len(), 2);
+        assert_eq!(model.layers[0].perceptrons[0].weights, pretrained);
+        let mut context = TrainingContext::new();
+        model.train(&mut context, &[0.1, 0.2, 0.3, 0.4], &[1.0, 0.0], 0.1);
+
+        model.reinitialize(0, &mut rng).unwrap();
+        assert_ne!(model.layers[0].perceptrons[0].weights, pretrained);
+        assert_eq!(model.layers[0].input_width(), 4);
+        assert!(matches!(model.reinitialize(3, &mut rng), Err(ModelError::LayerIndex { layer: 3, layers: 3 })));
+
+        let wrong = Layer::random(3, 1, Activation::Sigmoid, &mut rng);
+        assert!(matches!(model.append_layer(wrong), Err(ModelError::LayerMismatch { layer: 3, expected: 3, found: 2 })));
+        assert_eq!(model.layers.len(), 3);
+        assert!(model.append_layer(Layer::random(2, 1, Activation::Sigmoid, &mut rng)).is_ok());
+    }
+
+    #[test]
+    fn test_failed_surgery_leaves_model_unchanged() {
+        let mut rng = RunSeed(8).rng("init", 0);
+        let hidden = Layer::random(4, 3, Activation::ReLU, &mut rng);
+        let output = Layer::random(3, 1, Activation::Sigmoid, &mut rng);
+        let mut model = Model::new(4, vec![hidden, output]).unwrap();
+        let parameters = model.parameters();
+
+        assert!(matches!(model.truncate(0), Err(ModelError::NoLayers)));
+        assert!(matches!(
+            model.append_dense(&[(4, Activation::ReLU), (0, Activation::Sigmoid)], &mut rng),
+            Err(ModelError::EmptyLayer { layer: 3 })
+        ));
+        assert!(matches!(
+            model.replace_head(3, &[(1, Activation::Sigmoid)], &mut rng),
+            Err(ModelError::LayerIndex { layer: 3, layers: 2 })
+        ));
+        assert_eq!(model.layers.len(), 2);
+        assert_eq!(model.parameters(), parameters);
+
+        model.truncate(1).unwrap();
+        assert_eq!(model.output_width(), 3);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,93 @@
+use crate::ml::error::ModelError;
+use crate::ml::layer::{Layer, LayerCache};
+
+// Flat parameter vectors list the layers in order and, within a layer, each
+// neuron's weights followed by its bias.
+
+/// Number of weights and biases in `layers`.
+pub fn count(layers: &[Layer]) -> usize {
+    layers
+        .iter()
+        .flat_map(|layer| &layer.perceptrons)
+        .map(|perceptron| perceptron.weights.len() + 1)
+        .sum()
+}
+
+pub fn flatten(layers: &[Layer]) -> Vec<f64> {
+    let mut parameters = Vec::with_capacity(count(layers));
+    flatten_into(layers, &mut parameters);
+    parameters
+}
+
+/// Like `flatten`, but reuses the allocation of `parameters`.
+pub fn flatten_into(layers: &[Layer], parameters: &mut Vec<f64>) {
+    parameters.clear();
+    for perceptron in layers.iter().flat_map(|layer| &layer.perceptrons) {
+        parameters.extend_from_slice(&perceptron.weights);
+        parameters.push(perceptron.bias);
+    }
+}
+
+/// Writes a vector produced by `flatten` back into `layers`, then re-applies
+/// any pruning masks.
+pub fn unflatten(layers: &mut [Layer], parameters: &[f64]) -> Result<(), ModelError> {
+    let expected = count(layers);
+    if parameters.len() != expected {
+        return Err(ModelError::ParameterCount { expected, found: parameters.len() });
+    }
+
+    let mut values = parameters.iter().copied();
+    for layer in layers.iter_mut() {
+        for perceptron in &mut layer.perceptrons {
+            for (weight, value) in perceptron.weights.iter_mut().zip(&mut values) {
+                *weight = value;
+            }
+            perceptron.bias = values.next().unwrap_or(perceptron.bias);
+        }
+        layer.apply_mask();
+    }
+    Ok(())
+}
+
+/// The gradients stored in `caches`, in the same order as `flatten`. Weights
+/// removed by a pruning mask get a zero gradient.
+pub fn flatten_gradients(layers: &[Layer], caches: &[LayerCache], gradients: &mut Vec<f64>) {
+    gradients.clear();
+    for (layer, cache) in layers.iter().zip(caches) {
+        for (neuron, (weights, bias)) in cache.weight_gradients().iter().zip(cache.bias_gradients()).enumerate() {
+            let keep = layer.mask.as_ref().map(|mask| &mask[neuron]);
+            gradients.extend(
+                weights.iter().enumerate().map(|(i, g)| if keep.is_none_or(|keep| keep[i]) { *g } else { 0.0 }),
+            );
+            gradients.push(*bias);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::activation::Activation;
+    use crate::ml::perceptron::Perceptron;
+
+    #[test]
+    fn test_flatten_round_trip() {
+        let mut layers = vec![
+            Layer::new(vec![Perceptron::new(vec![1.0, 2.0], 3.0), Perceptron::new(vec![4.0, 5.0], 6.0)], Activation::ReLU),
+            Layer::new(vec![Perceptron::new(vec![7.0, 8.0], 9.0)], Activation::Sigmoid),
+        ];
+        layers[1].mask = Some(vec![vec![true, false]]);
+
+        let parameters = flatten(&layers);
+        assert_eq!(parameters, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]);
+        assert_eq!(count(&layers), 9);
+
+        let doubled: Vec<f64> = parameters.iter().map(|p| p * 2.0).collect();
+        unflatten(&mut layers, &doubled).unwrap();
+        // The masked weight stays zero
+        assert_eq!(flatten(&layers), vec![2.0, 4.0, 6.0, 8.0, 10.0, 12.0, 14.0, 0.0, 18.0]);
+
+        let error = unflatten(&mut layers, &[0.0; 3]).unwrap_err();
+        assert!(matches!(error, ModelError::ParameterCount { expected: 9, found: 3 }));
+    }
+}
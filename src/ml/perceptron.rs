@@ -1,3 +1,5 @@
+use crate::ml::simd;
+
 #[derive(Clone)]
 pub struct Perceptron {
     pub weights: Vec<f64>,  // one weight per input
@@ -8,12 +10,10 @@ impl Perceptron {
     pub fn new(weights: Vec<f64>, bias: f64) -> Self {
         Self { weights, bias }
     }
+    /// Weighted sum of `input` plus the bias. Like a zip, weights or inputs
+    /// past the shorter of the two are ignored.
     pub fn forward(&self, input: &[f64]) -> f64 {
-        let mut sum: f64 = 0.0;
-        
-        for i in 0..self.weights.len() {
-            sum += self.weights[i] * input[i];
-        }
-        sum + self.bias
+        let len = self.weights.len().min(input.len());
+        simd::dot(&self.weights[..len], &input[..len]) + self.bias
     }
 }
@@ -0,0 +1,279 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use crate::ml::activation::Activation;
+use crate::ml::error::LoadError;
+use crate::ml::layer::Layer;
+use crate::ml::model::Model;
+use crate::ml::perceptron::Perceptron;
+use crate::ml::seed::RunSeed;
+
+const HEADER: &str = "model v1";
+
+/// A model read back from disk with the seed of the run that produced it.
+pub struct SavedModel {
+    pub model: Model,
+    pub seed: RunSeed,
+}
+
+/// Writes the architecture and parameters of `model`, plus the run seed,
+/// as text:
+///
+/// ```text
+/// model v1
+/// seed 42
+/// input_width 57
+/// layer relu 32
+/// <bias> <weight> <weight> ...   (one line per neuron)
+/// layer sigmoid 1 frozen masked  (`frozen` for layers with `trainable` unset)
+/// <bias> <weight> <weight> ...
+/// mask 1011...                   (after each neuron of a `masked` layer)
+/// ```
+///
+/// Values are written with Rust's shortest round-trip formatting, so
+/// loading gives back bit-identical parameters. Training settings (loss,
+/// clipping, ...) are not stored; `Checkpoint` saves them alongside.
+pub fn save_model(model: &Model, seed: RunSeed, path: impl AsRef<Path>) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_model(&mut writer, model, seed)?;
+    writer.flush()
+}
+
+pub fn write_model(writer: &mut impl Write, model: &Model, seed: RunSeed) -> io::Result<()> {
+    writeln!(writer, "{}", HEADER)?;
+    writeln!(writer, "seed {}", seed)?;
+    writeln!(writer, "input_width {}", model.input_width())?;
+    for layer in &model.layers {
+        let frozen = if layer.trainable { "" } else { " frozen" };
+        let masked = if layer.mask.is_some() { " masked" } else { "" };
+        writeln!(writer, "layer {} {}{}{}", layer.activation.name(), layer.perceptrons.len(), frozen, masked)?;
+        for (neuron, perceptron) in layer.perceptrons.iter().enumerate() {
+            let values: Vec<String> =
+                std::iter::once(&perceptron.bias).chain(&perceptron.weights).map(f64::to_string).collect();
+            writeln!(writer, "{}", values.join(" "))?;
+            if let Some(mask) = &layer.mask {
+                let bits: String = mask[neuron].iter().map(|keep| if *keep { '1' } else { '0' }).collect();
+                writeln!(writer, "mask {}", bits)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn load_model(path: impl AsRef<Path>) -> Result<SavedModel, LoadError> {
+    let path = path.as_ref().display().to_string();
+    let file = File::open(&path).map_err(|source| LoadError::Io { path: path.clone(), source })?;
+    read_model(BufReader::new(file), &path)
+}
+
+/// Parses the format written by `write_model`. `path` is only used in
+/// error messages.
+pub fn read_model(reader: impl BufRead, path: &str) -> Result<SavedModel, LoadError> {
+    read_model_from(&mut LineReader::new(reader, path))
+}
+
+/// Reads a model section from `lines`, up to the end of the input.
+pub fn read_model_from<R: BufRead>(lines: &mut LineReader<R>) -> Result<SavedModel, LoadError> {
+    let header = lines.expect("header")?;
+    if header != HEADER {
+        return Err(lines.error(format!("expected {:?}, found {:?}", HEADER, header)));
+    }
+    let seed = RunSeed(lines.field("seed")?);
+    let input_width = lines.field("input_width")?;
+
+    let mut layers = Vec::new();
+    while let Some(line) = lines.next_line()? {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let (name, count, flags) = match parts.as_slice() {
+            ["layer", name, count, flags @ ..] if flags.iter().all(|flag| ["frozen", "masked"].contains(flag)) => {
+                (*name, *count, flags)
+            }
+            _ => {
+                return Err(lines.error(format!(
+                    "expected `layer <activation> <neurons> [frozen] [masked]`, found {:?}",
+                    line
+                )));
+            }
+        };
+        let masked = flags.contains(&"masked");
+        let activation = Activation::from_name(name)
+            .ok_or_else(|| lines.error(format!("unknown activation {:?}", name)))?;
+        let count: usize = lines.parse(count)?;
+
+        let mut perceptrons = Vec::with_capacity(count);
+        let mut mask = Vec::new();
+        for _ in 0..count {
+            let line = lines.expect("neuron")?;
+            let mut values = line.split_whitespace().map(|value| lines.parse(value)).collect::<Result<Vec<f64>, _>>()?;
+            if values.is_empty() {
+                return Err(lines.error("empty neuron line"));
+            }
+            let bias = values.remove(0);
+            if masked {
+                mask.push(read_mask(lines, values.len())?);
+            }
+            perceptrons.push(Perceptron::new(values, bias));
+        }
+        let mut layer = Layer::new(perceptrons, activation);
+        layer.trainable = !flags.contains(&"frozen");
+        layer.mask = masked.then_some(mask);
+        layers.push(layer);
+    }
+
+    let model = Model::new(input_width, layers).map_err(LoadError::Model)?;
+    Ok(SavedModel { model, seed })
+}
+
+/// Reads the `mask` line of a neuron with `width` weights.
+fn read_mask<R: BufRead>(lines: &mut LineReader<R>, width: usize) -> Result<Vec<bool>, LoadError> {
+    let line = lines.expect("mask")?;
+    let bits = line.strip_prefix("mask ").map(str::trim).unwrap_or_default();
+    if bits.len() != width || !bits.chars().all(|bit| bit == '0' || bit == '1') {
+        return Err(lines.error(format!("expected `mask` with {} bits, found {:?}", width, line)));
+    }
+    Ok(bits.chars().map(|bit| bit == '1').collect())
+}
+
+/// Non-blank, trimmed lines of a saved file, tracking the 1-based line
+/// number for error messages.
+pub struct LineReader<'a, R> {
+    path: &'a str,
+    lines: io::Lines<R>,
+    line: usize,
+}
+
+impl<'a, R: BufRead> LineReader<'a, R> {
+    pub fn new(reader: R, path: &'a str) -> Self {
+        Self { path, lines: reader.lines(), line: 0 }
+    }
+
+    /// The next non-blank line, or `None` at the end of the input.
+    pub fn next_line(&mut self) -> Result<Option<String>, LoadError> {
+        for line in self.lines.by_ref() {
+            self.line += 1;
+            let line = line.map_err(|source| LoadError::Io { path: self.path.to_string(), source })?;
+            if !line.trim().is_empty() {
+                return Ok(Some(line.trim().to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn expect(&mut self, what: &str) -> Result<String, LoadError> {
+        self.next_line()?.ok_or_else(|| LoadError::Format {
+            path: self.path.to_string(),
+            line: 0,
+            message: format!("unexpected end of file, expected {}", what),
+        })
+    }
+
+    /// Reads a `key value` line.
+    pub fn field<T: FromStr>(&mut self, key: &str) -> Result<T, LoadError> {
+        let line = self.expect(key)?;
+        match line.split_once(' ') {
+            Some((found, value)) if found == key => self.parse(value.trim()),
+            _ => Err(self.error(format!("expected `{} <value>`, found {:?}", key, line))),
+        }
+    }
+
+    pub fn parse<T: FromStr>(&self, value: &str) -> Result<T, LoadError> {
+        value.parse().map_err(|_| self.error(format!("invalid number {:?}", value)))
+    }
+
+    pub fn error(&self, message: impl Into<String>) -> LoadError {
+        LoadError::Format { path: self.path.to_string(), line: self.line, message: message.into() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_is_bit_identical() {
+        let mut rng = rand::rng();
+        let hidden = Layer::random(3, 4, Activation::ReLU, &mut rng);
+        let output = Layer::random(4, 1, Activation::Sigmoid, &mut rng);
+        let model = Model::new(3, vec![hidden, output]).unwrap();
+
+        let mut buffer = Vec::new();
+        write_model(&mut buffer, &model, RunSeed(42)).unwrap();
+        let saved = read_model(buffer.as_slice(), "model.txt").unwrap();
+
+        assert_eq!(saved.seed, RunSeed(42));
+        assert_eq!(saved.model.input_width(), 3);
+        for (original, loaded) in model.layers.iter().zip(&saved.model.layers) {
+            assert_eq!(original.activation, loaded.activation);
+            for (a, b) in original.perceptrons.iter().zip(&loaded.perceptrons) {
+                assert_eq!(a.bias.to_bits(), b.bias.to_bits());
+                assert_eq!(a.weights, b.weights);
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_file() {
+        let mut rng = RunSeed(5).rng("init", 0);
+        let model = Model::new(2, vec![Layer::random(2, 1, Activation::Sigmoid, &mut rng)]).unwrap();
+        let path = std::env::temp_dir().join("persistence_model.txt");
+
+        save_model(&model, RunSeed(5), &path).unwrap();
+        let saved = load_model(&path).unwrap();
+        assert_eq!(saved.seed, RunSeed(5));
+        assert_eq!(saved.model.parameters(), model.parameters());
+
+        let missing = std::env::temp_dir().join("persistence_missing_model.txt");
+        assert!(matches!(load_model(&missing), Err(LoadError::Io { .. })));
+    }
+
+    #[test]
+    fn test_round_trip_keeps_frozen_layers() {
+        let mut rng = rand::rng();
+        let mut model = Model::new(2, vec![
+            Layer::random(2, 3, Activation::ReLU, &mut rng),
+            Layer::random(3, 1, Activation::Sigmoid, &mut rng),
+        ])
+        .unwrap();
+        model.freeze(0);
+
+        let mut buffer = Vec::new();
+        write_model(&mut buffer, &model, RunSeed(7)).unwrap();
+        assert!(String::from_utf8_lossy(&buffer).contains("layer relu 3 frozen\n"));
+        let saved = read_model(buffer.as_slice(), "model.txt").unwrap();
+        let trainable: Vec<bool> = saved.model.layers.iter().map(|layer| layer.trainable).collect();
+        assert_eq!(trainable, vec![false, true]);
+    }
+
+    #[test]
+    fn test_round_trip_keeps_masks() {
+        let mut rng = rand::rng();
+        let mut model = Model::new(2, vec![
+            Layer::random(2, 2, Activation::ReLU, &mut rng),
+            Layer::random(2, 1, Activation::Sigmoid, &mut rng),
+        ])
+        .unwrap();
+        model.layers[0].mask = Some(vec![vec![true, false], vec![false, true]]);
+        model.layers[0].apply_mask();
+
+        let mut buffer = Vec::new();
+        write_model(&mut buffer, &model, RunSeed(7)).unwrap();
+        let saved = read_model(buffer.as_slice(), "model.txt").unwrap();
+        assert_eq!(saved.model.layers[0].mask, model.layers[0].mask);
+        assert_eq!(saved.model.layers[1].mask, None);
+
+        let text = "model v1\nseed 1\ninput_width 2\nlayer relu 1 masked\n0.5 1.0 0.0\nmask 1\n";
+        let error = read_model(text.as_bytes(), "model.txt").err().unwrap();
+        assert!(error.to_string().starts_with("model.txt:6: expected `mask` with 2 bits"));
+    }
+
+    #[test]
+    fn test_read_reports_line() {
+        let text = "model v1\nseed 1\ninput_width 2\nlayer relu 1\n0.5 1.0 abc\n";
+        let error = read_model(text.as_bytes(), "model.txt").err().unwrap();
+        assert_eq!(error.to_string(), "model.txt:5: invalid number \"abc\"");
+
+        let text = "model v1\nseed 1\ninput_width 3\nlayer relu 1\n0.5 1.0 2.0\n";
+        assert!(matches!(read_model(text.as_bytes(), "model.txt"), Err(LoadError::Model(_))));
+    }
+}
@@ -0,0 +1,26 @@
+use crate::ml::metrics::{self, RegressionMetrics};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Evaluation {
+    pub accuracy: f64,
+    pub regression: RegressionMetrics,
+}
+
+/// Anything that maps an input to an output vector and can be scored with
+/// the metrics in `metrics`.
+pub trait Predictor {
+    fn predict(&self, input: &[f64]) -> Vec<f64>;
+
+    fn predict_all(&self, data: &[(Vec<f64>, Vec<f64>)]) -> Vec<Vec<f64>> {
+        data.iter().map(|(input, _)| self.predict(input)).collect()
+    }
+
+    fn evaluate(&self, data: &[(Vec<f64>, Vec<f64>)]) -> Evaluation {
+        let outputs = self.predict_all(data);
+        let targets = metrics::targets(data);
+        Evaluation {
+            accuracy: metrics::accuracy(&outputs, &targets),
+            regression: metrics::regression_metrics(&outputs, &targets),
+        }
+    }
+}
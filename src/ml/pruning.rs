@@ -0,0 +1,327 @@
+use crate::ml::activation::Activation;
+use crate::ml::layer::Layer;
+use crate::ml::model::Model;
+use crate::ml::predictor::Predictor;
+use crate::ml::training::TrainingContext;
+
+/// Prunes the smallest-magnitude weights across all layers so that
+/// `sparsity` of the model's weights are zero. Pruned weights are recorded
+/// in each layer's mask and stay at zero during further training.
+pub fn prune_global(model: &mut Model, sparsity: f64) {
+    let mut entries = Vec::new();
+    for (layer_index, layer) in model.layers.iter().enumerate() {
+        collect_magnitudes(layer, layer_index, &mut entries);
+    }
+    let count = target_count(entries.len(), sparsity);
+    prune_smallest(&mut model.layers, entries, count);
+}
+
+/// Prunes `sparsity` of the weights of every layer independently.
+pub fn prune_per_layer(model: &mut Model, sparsity: f64) {
+    for layer_index in 0..model.layers.len() {
+        let mut entries = Vec::new();
+        collect_magnitudes(&model.layers[layer_index], layer_index, &mut entries);
+        let count = target_count(entries.len(), sparsity);
+        prune_smallest(&mut model.layers, entries, count);
+    }
+}
+
+/// Fraction of weights (biases excluded) that are exactly zero.
+pub fn sparsity(model: &Model) -> f64 {
+    let weights = model.layers.iter().flat_map(|layer| &layer.perceptrons).flat_map(|p| &p.weights);
+    let (total, zeros) = weights.fold((0, 0), |(total, zeros), &w| (total + 1, zeros + (w == 0.0) as usize));
+    if total == 0 { 0.0 } else { zeros as f64 / total as f64 }
+}
+
+/// Removes hidden neurons that cannot influence the output: those whose
+/// outgoing weights are all zero, and those whose incoming weights are all
+/// zero (their constant output is folded into the next layer's biases).
+/// The next layer is shrunk to match. Returns the number of neurons removed.
+pub fn remove_dead_neurons(model: &mut Model) -> usize {
+    let mut removed = 0;
+    loop {
+        let mut changed = false;
+        for layer_index in 0..model.layers.len().saturating_sub(1) {
+            let (head, tail) = model.layers.split_at_mut(layer_index + 1);
+            let layer = &mut head[layer_index];
+            let next = &mut tail[0];
+
+            for neuron in (0..layer.perceptrons.len()).rev() {
+                if layer.perceptrons.len() == 1 {
+                    break;
+                }
+                let outgoing_dead = next.perceptrons.iter().all(|p| p.weights[neuron] == 0.0);
+                let incoming_dead = layer.perceptrons[neuron].weights.iter().all(|&w| w == 0.0);
+                if !outgoing_dead && !incoming_dead {
+                    continue;
+                }
+
+                if !outgoing_dead {
+                    let constant = layer.activation.activate(layer.perceptrons[neuron].bias);
+                    for perceptron in &mut next.perceptrons {
+                        perceptron.bias += perceptron.weights[neuron] * constant;
+                    }
+                }
+                layer.remove_neuron(neuron);
+                next.remove_input(neuron);
+                removed += 1;
+                changed = true;
+            }
+        }
+        if !changed {
+            return removed;
+        }
+    }
+}
+
+/// Inference-only copy of a model that stores just the non-zero weights.
+pub struct SparseModel {
+    layers: Vec<SparseLayer>,
+}
+
+struct SparseLayer {
+    rows: Vec<SparseRow>,
+    activation: Activation,
+}
+
+struct SparseRow {
+    indices: Vec<usize>,
+    values: Vec<f64>,
+    bias: f64,
+}
+
+impl SparseModel {
+    pub fn from_model(model: &Model) -> Self {
+        let layers = model
+            .layers
+            .iter()
+            .map(|layer| SparseLayer {
+                rows: layer
+                    .perceptrons
+                    .iter()
+                    .map(|perceptron| {
+                        let (indices, values) = perceptron
+                            .weights
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, w)| **w != 0.0)
+                            .unzip();
+                        SparseRow { indices, values, bias: perceptron.bias }
+                    })
+                    .collect(),
+                activation: layer.activation,
+            })
+            .collect();
+        Self { layers }
+    }
+
+    pub fn forward(&self, input: &[f64]) -> Vec<f64> {
+        let mut current = input.to_vec();
+        for layer in &self.layers {
+            current = layer
+                .rows
+                .iter()
+                .map(|row| {
+                    let sum: f64 = row.indices.iter().zip(&row.values).map(|(&i, w)| w * current[i]).sum();
+                    layer.activation.activate(sum + row.bias)
+                })
+                .collect();
+        }
+        current
+    }
+
+    pub fn nonzero_weights(&self) -> usize {
+        self.layers.iter().flat_map(|layer| &layer.rows).map(|row| row.values.len()).sum()
+    }
+}
+
+impl Predictor for SparseModel {
+    fn predict(&self, input: &[f64]) -> Vec<f64> {
+        self.forward(input)
+    }
+}
+
+/// Optional training run after each pruning step, with the masks fixed.
+pub struct FineTune<'a> {
+    pub data: &'a [(Vec<f64>, Vec<f64>)],
+    pub learning_rate: f64,
+    pub epochs: usize,
+}
+
+pub struct SparsityPoint {
+    pub target_sparsity: f64,
+    pub sparsity: f64,
+    pub neurons_removed: usize,
+    pub nonzero_weights: usize,
+    pub accuracy: f64,
+}
+
+/// Prunes a copy of `model` to every level in `levels` with `prune`, e.g.
+/// `prune_global` or `prune_per_layer`, and measures the sparse model's
+/// accuracy on `test_data`.
+pub fn sparsity_tradeoff(
+    model: &Model,
+    prune: fn(&mut Model, f64),
+    levels: &[f64],
+    test_data: &[(Vec<f64>, Vec<f64>)],
+    fine_tune: Option<&FineTune>,
+) -> Vec<SparsityPoint> {
+    let mut context = TrainingContext::new();
+    levels
+        .iter()
+        .map(|&target_sparsity| {
+            let mut pruned = model.clone();
+            prune(&mut pruned, target_sparsity);
+            if let Some(fine_tune) = fine_tune {
+                for _ in 0..fine_tune.epochs {
+                    pruned.train_epoch(&mut context, fine_tune.data, fine_tune.learning_rate);
+                }
+            }
+            let neurons_removed = remove_dead_neurons(&mut pruned);
+            let sparse = SparseModel::from_model(&pruned);
+            SparsityPoint {
+                target_sparsity,
+                sparsity: sparsity(&pruned),
+                neurons_removed,
+                nonzero_weights: sparse.nonzero_weights(),
+                accuracy: sparse.evaluate(test_data).accuracy,
+            }
+        })
+        .collect()
+}
+
+pub fn print_tradeoff(points: &[SparsityPoint]) {
+    println!("{:>8} {:>8} {:>9} {:>8} {:>9}", "target", "actual", "weights", "removed", "accuracy");
+    for point in points {
+        println!(
+            "{:>8.2} {:>8.3} {:>9} {:>8} {:>9.4}",
+            point.target_sparsity, point.sparsity, point.nonzero_weights, point.neurons_removed, point.accuracy
+        );
+    }
+}
+
+// (magnitude, layer, neuron, weight)
+type WeightEntry = (f64, usize, usize, usize);
+
+fn collect_magnitudes(layer: &Layer, layer_index: usize, entries: &mut Vec<WeightEntry>) {
+    for (neuron, perceptron) in layer.perceptrons.iter().enumerate() {
+        for (j, weight) in perceptron.weights.iter().enumerate() {
+            entries.push((weight.abs(), layer_index, neuron, j));
+        }
+    }
+}
+
+fn target_count(total: usize, sparsity: f64) -> usize {
+    ((total as f64 * sparsity.clamp(0.0, 1.0)).round() as usize).min(total)
+}
+
+fn prune_smallest(layers: &mut [Layer], mut entries: Vec<WeightEntry>, count: usize) {
+    entries.sort_by(|a, b| a.0.total_cmp(&b.0));
+    for &(_, layer_index, neuron, j) in entries.iter().take(count) {
+        let layer = &mut layers[layer_index];
+        let mask = layer.mask.get_or_insert_with(|| {
+            layer.perceptrons.iter().map(|p| vec![true; p.weights.len()]).collect()
+        });
+        mask[neuron][j] = false;
+    }
+    for layer in layers {
+        layer.apply_mask();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::perceptron::Perceptron;
+
+    fn small_model() -> Model {
+        let hidden = Layer::new(vec![
+            Perceptron::new(vec![0.9, -0.1], 0.1),
+            Perceptron::new(vec![0.05, 0.02], 0.3),
+            Perceptron::new(vec![-0.7, 0.4], 0.0),
+        ], Activation::Sigmoid);
+        let output = Layer::new(vec![
+            Perceptron::new(vec![0.5, 0.8, -0.6], 0.1),
+        ], Activation::Sigmoid);
+        Model::new(2, vec![hidden, output]).unwrap()
+    }
+
+    #[test]
+    fn test_prune_global_reaches_target() {
+        let mut model = small_model();
+        prune_global(&mut model, 0.5);
+
+        // 9 weights -> round(4.5) = 5 pruned, the smallest magnitudes first
+        assert!((sparsity(&model) - 5.0 / 9.0).abs() < 1e-10);
+        assert_eq!(model.layers[0].perceptrons[1].weights, vec![0.0, 0.0]);
+        assert_eq!(model.layers[0].perceptrons[0].weights[0], 0.9);
+    }
+
+    #[test]
+    fn test_prune_per_layer() {
+        let mut model = small_model();
+        prune_per_layer(&mut model, 1.0 / 3.0);
+
+        let zeros = |layer: &Layer| layer.perceptrons.iter().flat_map(|p| &p.weights).filter(|w| **w == 0.0).count();
+        assert_eq!(zeros(&model.layers[0]), 2);
+        assert_eq!(zeros(&model.layers[1]), 1);
+    }
+
+    #[test]
+    fn test_masks_survive_fine_tuning() {
+        let mut model = small_model();
+        prune_global(&mut model, 0.5);
+        let data = vec![(vec![1.0, 0.0], vec![1.0]), (vec![0.0, 1.0], vec![0.0])];
+        let mut context = TrainingContext::new();
+        for _ in 0..10 {
+            model.train_epoch(&mut context, &data, 0.5);
+        }
+        assert!((sparsity(&model) - 5.0 / 9.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_remove_dead_neurons_keeps_outputs() {
+        let mut model = small_model();
+        // Neuron 1 only has incoming zeros, neuron 2 only outgoing zeros
+        model.layers[0].perceptrons[1].weights = vec![0.0, 0.0];
+        model.layers[1].perceptrons[0].weights[2] = 0.0;
+
+        let input = [0.3, 0.8];
+        let before = model.forward(&input);
+        assert_eq!(remove_dead_neurons(&mut model), 2);
+        assert_eq!(model.layers[0].perceptrons.len(), 1);
+        assert_eq!(model.layers[1].perceptrons[0].weights.len(), 1);
+
+        let after = model.forward(&input);
+        assert!((before[0] - after[0]).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_sparse_forward_matches_dense() {
+        let mut model = small_model();
+        prune_global(&mut model, 0.4);
+        let sparse = SparseModel::from_model(&model);
+
+        let input = [0.6, -0.2];
+        let dense = model.forward(&input);
+        assert!((sparse.forward(&input)[0] - dense[0]).abs() < 1e-12);
+        assert_eq!(sparse.nonzero_weights(), 5);
+
+        let data = vec![(input.to_vec(), vec![1.0]), (vec![-0.4, 0.9], vec![0.0])];
+        assert_eq!(sparse.evaluate(&data).accuracy, model.evaluate(&data).accuracy);
+    }
+
+    #[test]
+    fn test_sparsity_tradeoff() {
+        let model = small_model();
+        let data = vec![(vec![1.0, 0.0], vec![1.0]), (vec![0.0, 1.0], vec![0.0])];
+        let fine_tune = FineTune { data: &data, learning_rate: 0.5, epochs: 2 };
+        let points = sparsity_tradeoff(&model, prune_per_layer, &[0.0, 1.0 / 3.0, 1.0], &data, Some(&fine_tune));
+
+        assert_eq!(points.len(), 3);
+        assert_eq!((points[0].neurons_removed, points[0].nonzero_weights), (0, 9));
+        // Neuron 1 loses its inputs and neuron 0 its output, so both go
+        assert_eq!((points[1].neurons_removed, points[1].nonzero_weights), (2, 3));
+        assert_eq!((points[2].sparsity, points[2].nonzero_weights), (1.0, 0));
+    }
+}
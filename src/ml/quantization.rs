@@ -0,0 +1,248 @@
+use crate::ml::activation::Activation;
+use crate::ml::layer::Layer;
+use crate::ml::metrics;
+use crate::ml::model::Model;
+
+const INT8_MAX: f64 = 127.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScaleGranularity {
+    /// One weight scale for the whole layer.
+    PerLayer,
+    /// One weight scale per neuron (output channel).
+    PerChannel,
+}
+
+/// A dense layer with int8 weights. Inputs are quantized with a scale fixed
+/// during calibration, dot products accumulate in `i32`, and biases are
+/// stored pre-scaled in the accumulator's units.
+pub struct QuantizedLayer {
+    weights: Vec<Vec<i8>>,
+    biases: Vec<i32>,
+    weight_scales: Vec<f64>,
+    input_scale: f64,
+    activation: Activation,
+}
+
+impl QuantizedLayer {
+    fn from_layer(layer: &Layer, input_range: f64, granularity: ScaleGranularity) -> Self {
+        let channel_ranges: Vec<f64> = layer
+            .perceptrons
+            .iter()
+            .map(|p| p.weights.iter().fold(0.0, |max: f64, w| max.max(w.abs())))
+            .collect();
+        let weight_scales: Vec<f64> = match granularity {
+            ScaleGranularity::PerChannel => channel_ranges.iter().map(|&range| scale_for(range)).collect(),
+            ScaleGranularity::PerLayer => {
+                let range = channel_ranges.iter().fold(0.0, |max: f64, &r| max.max(r));
+                vec![scale_for(range); layer.perceptrons.len()]
+            }
+        };
+        let input_scale = scale_for(input_range);
+
+        let weights = layer
+            .perceptrons
+            .iter()
+            .zip(&weight_scales)
+            .map(|(p, &scale)| p.weights.iter().map(|&w| quantize(w, scale)).collect())
+            .collect();
+        let biases = layer
+            .perceptrons
+            .iter()
+            .zip(&weight_scales)
+            .map(|(p, &scale)| (p.bias / (input_scale * scale)).round() as i32)
+            .collect();
+
+        Self { weights, biases, weight_scales, input_scale, activation: layer.activation }
+    }
+
+    pub fn forward(&self, input: &[f64]) -> Vec<f64> {
+        let quantized_input: Vec<i8> = input.iter().map(|&x| quantize(x, self.input_scale)).collect();
+        self.weights
+            .iter()
+            .zip(&self.biases)
+            .zip(&self.weight_scales)
+            .map(|((weights, &bias), &scale)| {
+                let accumulator: i32 = weights
+                    .iter()
+                    .zip(&quantized_input)
+                    .map(|(&w, &x)| w as i32 * x as i32)
+                    .sum::<i32>()
+                    + bias;
+                self.activation.activate(accumulator as f64 * self.input_scale * scale)
+            })
+            .collect()
+    }
+}
+
+pub struct QuantizedModel {
+    pub layers: Vec<QuantizedLayer>,
+}
+
+impl QuantizedModel {
+    /// Quantizes every layer of `model`. Input scales come from the largest
+    /// absolute value each layer sees on up to `samples` evenly spaced rows
+    /// of `calibration_data` (usually `Dataset.train_data`).
+    pub fn calibrate(
+        model: &Model,
+        calibration_data: &[(Vec<f64>, Vec<f64>)],
+        samples: usize,
+        granularity: ScaleGranularity,
+    ) -> Self {
+        let mut input_ranges = vec![0.0f64; model.layers.len()];
+        let step = (calibration_data.len() / samples.max(1)).max(1);
+
+        for (input, _) in calibration_data.iter().step_by(step).take(samples) {
+            let mut current = input.clone();
+            for (range, layer) in input_ranges.iter_mut().zip(&model.layers) {
+                *range = current.iter().fold(*range, |max, x| max.max(x.abs()));
+                current = layer.forward(&current);
+            }
+        }
+
+        let layers = model
+            .layers
+            .iter()
+            .zip(&input_ranges)
+            .map(|(layer, &range)| QuantizedLayer::from_layer(layer, range, granularity))
+            .collect();
+        Self { layers }
+    }
+
+    pub fn forward(&self, input: &[f64]) -> Vec<f64> {
+        let mut current = input.to_vec();
+        for layer in &self.layers {
+            current = layer.forward(&current);
+        }
+        current
+    }
+
+    /// Bytes used by the int8 weights and i32 biases.
+    pub fn weight_bytes(&self) -> usize {
+        self.layers
+            .iter()
+            .map(|layer| layer.weights.iter().map(Vec::len).sum::<usize>() + layer.biases.len() * 4)
+            .sum()
+    }
+}
+
+pub struct DriftReport {
+    pub float_accuracy: f64,
+    pub quantized_accuracy: f64,
+    /// Fraction of samples where both models predict the same class.
+    pub agreement: f64,
+    pub mean_abs_error: f64,
+    pub max_abs_error: f64,
+}
+
+impl DriftReport {
+    pub fn accuracy_drift(&self) -> f64 {
+        self.quantized_accuracy - self.float_accuracy
+    }
+
+    pub fn print(&self) {
+        println!("float accuracy:     {:.4}", self.float_accuracy);
+        println!("quantized accuracy: {:.4} ({:+.4})", self.quantized_accuracy, self.accuracy_drift());
+        println!("class agreement:    {:.4}", self.agreement);
+        println!("output error:       mean {:.6}, max {:.6}", self.mean_abs_error, self.max_abs_error);
+    }
+}
+
+/// Compares the float model against its quantized copy on `test_data`.
+pub fn drift_report(model: &Model, quantized: &QuantizedModel, test_data: &[(Vec<f64>, Vec<f64>)]) -> DriftReport {
+    let float_outputs: Vec<Vec<f64>> = test_data.iter().map(|(input, _)| model.forward(input)).collect();
+    let quantized_outputs: Vec<Vec<f64>> = test_data.iter().map(|(input, _)| quantized.forward(input)).collect();
+    let targets: Vec<Vec<f64>> = test_data.iter().map(|(_, target)| target.clone()).collect();
+
+    let errors: Vec<f64> = float_outputs
+        .iter()
+        .zip(&quantized_outputs)
+        .flat_map(|(f, q)| f.iter().zip(q).map(|(a, b)| (a - b).abs()))
+        .collect();
+    let agreeing = float_outputs
+        .iter()
+        .zip(&quantized_outputs)
+        .filter(|(f, q)| metrics::predicted_class(f) == metrics::predicted_class(q))
+        .count();
+
+    DriftReport {
+        float_accuracy: metrics::accuracy(&float_outputs, &targets),
+        quantized_accuracy: metrics::accuracy(&quantized_outputs, &targets),
+        agreement: agreeing as f64 / test_data.len().max(1) as f64,
+        mean_abs_error: errors.iter().sum::<f64>() / errors.len().max(1) as f64,
+        max_abs_error: errors.iter().fold(0.0, |max: f64, &e| max.max(e)),
+    }
+}
+
+fn scale_for(range: f64) -> f64 {
+    if range > 0.0 { range / INT8_MAX } else { 1.0 }
+}
+
+fn quantize(value: f64, scale: f64) -> i8 {
+    (value / scale).round().clamp(-INT8_MAX, INT8_MAX) as i8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::perceptron::Perceptron;
+
+    fn small_model() -> Model {
+        let hidden = Layer::new(vec![
+            Perceptron::new(vec![0.9, -0.3], 0.1),
+            Perceptron::new(vec![0.04, 0.02], 0.0),
+        ], Activation::ReLU);
+        let output = Layer::new(vec![
+            Perceptron::new(vec![1.5, 4.0], -0.2),
+        ], Activation::Sigmoid);
+        Model::new(2, vec![hidden, output]).unwrap()
+    }
+
+    fn calibration_data() -> Vec<(Vec<f64>, Vec<f64>)> {
+        (0..20).map(|i| (vec![i as f64 / 20.0, 1.0 - i as f64 / 20.0], vec![0.0])).collect()
+    }
+
+    #[test]
+    fn test_quantize_clamps() {
+        assert_eq!(quantize(1.0, 1.0 / 127.0), 127);
+        assert_eq!(quantize(-5.0, 1.0 / 127.0), -127);
+        assert_eq!(quantize(0.0, 0.5), 0);
+    }
+
+    #[test]
+    fn test_calibration_clips_unseen_range() {
+        let model = small_model();
+        let data = calibration_data();
+        let quantized = QuantizedModel::calibrate(&model, &data, 10, ScaleGranularity::PerChannel);
+
+        // Every second row is used, so the output layer never sees the
+        // hidden layer's largest activation (0.94 on the last row)
+        assert!((quantized.layers[0].input_scale - 1.0 / 127.0).abs() < 1e-12);
+        assert!(quantized.layers[1].input_scale < 0.94 / 127.0);
+    }
+
+    #[test]
+    fn test_quantized_forward_close_to_float() {
+        let model = small_model();
+        let data = calibration_data();
+        let quantized = QuantizedModel::calibrate(&model, &data, data.len(), ScaleGranularity::PerChannel);
+
+        for (input, _) in &data {
+            let expected = model.forward(input)[0];
+            assert!((quantized.forward(input)[0] - expected).abs() < 0.01);
+        }
+        assert_eq!(quantized.weight_bytes(), 6 + 3 * 4);
+    }
+
+    #[test]
+    fn test_per_channel_beats_per_layer() {
+        let model = small_model();
+        let data = calibration_data();
+        let per_layer = QuantizedModel::calibrate(&model, &data, 20, ScaleGranularity::PerLayer);
+        let per_channel = QuantizedModel::calibrate(&model, &data, 20, ScaleGranularity::PerChannel);
+
+        let layer_report = drift_report(&model, &per_layer, &data);
+        let channel_report = drift_report(&model, &per_channel, &data);
+        assert!(channel_report.mean_abs_error < layer_report.mean_abs_error);
+    }
+}
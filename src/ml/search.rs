@@ -0,0 +1,481 @@
+use std::cmp::Ordering;
+use std::io;
+use std::path::Path;
+use std::thread;
+use rand::Rng;
+use crate::ml::activation::Activation;
+use crate::ml::error::SearchError;
+use crate::ml::layer::Layer;
+use crate::ml::metrics;
+use crate::ml::model::Model;
+use crate::ml::seed::RunSeed;
+use crate::ml::training::TrainingContext;
+
+/// Candidate values for each hyperparameter.
+pub struct SearchSpace {
+    pub learning_rates: Vec<f64>,
+    pub hidden_layers: Vec<Vec<usize>>,
+    pub activations: Vec<Activation>,
+    pub batch_sizes: Vec<usize>,
+    pub weight_decays: Vec<f64>,
+}
+
+impl SearchSpace {
+    /// Every combination of the candidate values.
+    pub fn grid(&self) -> Vec<TrialConfig> {
+        let mut configs = Vec::new();
+        for &learning_rate in &self.learning_rates {
+            for hidden_layers in &self.hidden_layers {
+                for &activation in &self.activations {
+                    for &batch_size in &self.batch_sizes {
+                        for &weight_decay in &self.weight_decays {
+                            configs.push(TrialConfig {
+                                learning_rate,
+                                hidden_layers: hidden_layers.clone(),
+                                activation,
+                                batch_size,
+                                weight_decay,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        configs
+    }
+
+    /// Checks that every hyperparameter has at least one candidate and that
+    /// each candidate can be trained with.
+    pub fn validate(&self) -> Result<(), SearchError> {
+        non_empty("learning_rate", &self.learning_rates)?;
+        non_empty("hidden_layers", &self.hidden_layers)?;
+        non_empty("activation", &self.activations)?;
+        non_empty("batch_size", &self.batch_sizes)?;
+        non_empty("weight_decay", &self.weight_decays)?;
+
+        let invalid = |parameter, value: &dyn std::fmt::Debug| {
+            Err(SearchError::InvalidCandidate { parameter, value: format!("{:?}", value) })
+        };
+        if let Some(rate) = self.learning_rates.iter().find(|rate| !(rate.is_finite() && **rate > 0.0)) {
+            return invalid("learning_rate", rate);
+        }
+        if let Some(widths) = self.hidden_layers.iter().find(|widths| widths.contains(&0)) {
+            return invalid("hidden_layers", widths);
+        }
+        if let Some(size) = self.batch_sizes.iter().find(|size| **size == 0) {
+            return invalid("batch_size", size);
+        }
+        if let Some(decay) = self.weight_decays.iter().find(|decay| !(decay.is_finite() && **decay >= 0.0)) {
+            return invalid("weight_decay", decay);
+        }
+        Ok(())
+    }
+
+    /// One value drawn uniformly from each candidate list. Only called on
+    /// a validated space, so no list is empty.
+    fn sample(&self, rng: &mut impl Rng) -> TrialConfig {
+        TrialConfig {
+            learning_rate: pick(&self.learning_rates, rng),
+            hidden_layers: pick(&self.hidden_layers, rng),
+            activation: pick(&self.activations, rng),
+            batch_size: pick(&self.batch_sizes, rng),
+            weight_decay: pick(&self.weight_decays, rng),
+        }
+    }
+}
+
+fn non_empty<T>(parameter: &'static str, values: &[T]) -> Result<(), SearchError> {
+    if values.is_empty() { Err(SearchError::NoCandidates { parameter }) } else { Ok(()) }
+}
+
+fn pick<T: Clone>(values: &[T], rng: &mut impl Rng) -> T {
+    values[rng.random_range(0..values.len())].clone()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrialConfig {
+    pub learning_rate: f64,
+    pub hidden_layers: Vec<usize>,
+    pub activation: Activation,
+    pub batch_size: usize,
+    pub weight_decay: f64,
+}
+
+impl TrialConfig {
+    /// Randomly initialized hidden layers followed by a sigmoid output layer.
+    pub fn build_model(&self, input_width: usize, output_width: usize, rng: &mut impl Rng) -> Model {
+        let mut layers = Vec::new();
+        let mut width = input_width;
+        for &neurons in &self.hidden_layers {
+            layers.push(Layer::random(width, neurons, self.activation, rng));
+            width = neurons;
+        }
+        layers.push(Layer::random(width, output_width, Activation::Sigmoid, rng));
+
+        Model::new(input_width, layers)
+            .expect("generated layers connect")
+            .with_weight_decay(self.weight_decay)
+    }
+
+    fn hidden_layers_label(&self) -> String {
+        let widths: Vec<String> = self.hidden_layers.iter().map(|w| w.to_string()).collect();
+        widths.join("-")
+    }
+}
+
+pub enum Strategy {
+    /// Every combination, each trained for `epochs`.
+    Grid { epochs: usize },
+    /// `trials` random configurations, each trained for `epochs`.
+    Random { trials: usize, epochs: usize },
+    /// `trials` random configurations trained for `min_epochs`; the best
+    /// `1 / reduction_factor` continue with `reduction_factor` times the
+    /// budget until one is left.
+    SuccessiveHalving { trials: usize, min_epochs: usize, reduction_factor: usize },
+    /// Successive-halving brackets trading the number of configurations
+    /// against their starting budget, up to `max_epochs` per trial.
+    Hyperband { max_epochs: usize, reduction_factor: usize },
+}
+
+/// Validation metric that orders the trials.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Ranking {
+    /// Lowest mean loss first. Unlike accuracy it still separates trials
+    /// that classify the same samples correctly.
+    #[default]
+    ValidationLoss,
+    /// Highest accuracy first.
+    ValidationAccuracy,
+}
+
+impl Ranking {
+    /// `Less` when `a` ranks before `b`.
+    pub fn compare(&self, a: &TrialResult, b: &TrialResult) -> Ordering {
+        match self {
+            Ranking::ValidationLoss => a.validation_loss.total_cmp(&b.validation_loss),
+            Ranking::ValidationAccuracy => b.validation_accuracy.total_cmp(&a.validation_accuracy),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TrialResult {
+    pub config: TrialConfig,
+    pub epochs: usize,
+    pub validation_accuracy: f64,
+    pub validation_loss: f64,
+}
+
+struct Trial {
+    model: Model,
+    result: TrialResult,
+}
+
+pub struct Search<'a> {
+    space: SearchSpace,
+    train_data: &'a [(Vec<f64>, Vec<f64>)],
+    validation_data: &'a [(Vec<f64>, Vec<f64>)],
+    /// Number of trials trained at the same time.
+    pub threads: usize,
+    /// Drives config sampling and initialization; the result does not
+    /// depend on `threads`.
+    pub seed: RunSeed,
+    pub ranking: Ranking,
+}
+
+impl<'a> Search<'a> {
+    /// Single-threaded search ranked by validation loss. Fails if `space`
+    /// does not pass `SearchSpace::validate`.
+    pub fn new(
+        space: SearchSpace,
+        train_data: &'a [(Vec<f64>, Vec<f64>)],
+        validation_data: &'a [(Vec<f64>, Vec<f64>)],
+        seed: RunSeed,
+    ) -> Result<Self, SearchError> {
+        space.validate()?;
+        Ok(Self { space, train_data, validation_data, threads: 1, seed, ranking: Ranking::default() })
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn with_ranking(mut self, ranking: Ranking) -> Self {
+        self.ranking = ranking;
+        self
+    }
+
+    /// Runs the search and returns every trial, best first by `ranking`.
+    pub fn run(&self, strategy: &Strategy) -> Vec<TrialResult> {
+        let mut rng = self.seed.rng("search", 0);
+        let mut results = match *strategy {
+            Strategy::Grid { epochs } => self.train_all(self.space.grid(), epochs, &mut rng),
+            Strategy::Random { trials, epochs } => {
+                let configs = (0..trials).map(|_| self.space.sample(&mut rng)).collect();
+                self.train_all(configs, epochs, &mut rng)
+            }
+            Strategy::SuccessiveHalving { trials, min_epochs, reduction_factor } => {
+                let configs = (0..trials).map(|_| self.space.sample(&mut rng)).collect();
+                self.successive_halving(configs, min_epochs, reduction_factor, usize::MAX, &mut rng)
+            }
+            Strategy::Hyperband { max_epochs, reduction_factor } => {
+                let eta = reduction_factor.max(2);
+                let s_max = (max_epochs.max(1) as f64).log(eta as f64).floor() as u32;
+                let mut results = Vec::new();
+                for s in (0..=s_max).rev() {
+                    let trials = ((s_max + 1) as f64 / (s + 1) as f64 * eta.pow(s) as f64).ceil() as usize;
+                    let min_epochs = (max_epochs / eta.pow(s)).max(1);
+                    let configs = (0..trials).map(|_| self.space.sample(&mut rng)).collect();
+                    results.extend(self.successive_halving(configs, min_epochs, eta, max_epochs, &mut rng));
+                }
+                results
+            }
+        };
+        results.sort_by(|a, b| self.ranking.compare(a, b));
+        results
+    }
+
+    fn train_all(&self, configs: Vec<TrialConfig>, epochs: usize, rng: &mut impl Rng) -> Vec<TrialResult> {
+        let mut trials = self.start_trials(configs, rng);
+        self.train_trials(&mut trials, epochs);
+        trials.into_iter().map(|trial| trial.result).collect()
+    }
+
+    fn successive_halving(
+        &self,
+        configs: Vec<TrialConfig>,
+        min_epochs: usize,
+        reduction_factor: usize,
+        max_epochs: usize,
+        rng: &mut impl Rng,
+    ) -> Vec<TrialResult> {
+        let mut trials = self.start_trials(configs, rng);
+        let mut finished = Vec::new();
+        let mut budget = min_epochs.max(1).min(max_epochs);
+
+        loop {
+            self.train_trials(&mut trials, budget);
+            trials.sort_by(|a, b| self.ranking.compare(&a.result, &b.result));
+
+            let next_budget = budget.saturating_mul(reduction_factor.max(2));
+            if trials.len() <= 1 || next_budget > max_epochs {
+                break;
+            }
+            let keep = (trials.len() / reduction_factor.max(2)).max(1);
+            finished.extend(trials.drain(keep..).map(|trial| trial.result));
+            budget = next_budget;
+        }
+
+        finished.extend(trials.into_iter().map(|trial| trial.result));
+        finished
+    }
+
+    fn start_trials(&self, configs: Vec<TrialConfig>, rng: &mut impl Rng) -> Vec<Trial> {
+        let input_width = self.train_data.first().map_or(0, |(input, _)| input.len());
+        let output_width = self.train_data.first().map_or(1, |(_, target)| target.len());
+
+        configs
+            .into_iter()
+            .map(|config| Trial {
+                model: config.build_model(input_width, output_width, rng),
+                result: TrialResult { config, epochs: 0, validation_accuracy: 0.0, validation_loss: f64::INFINITY },
+            })
+            .collect()
+    }
+
+    /// Trains every trial up to `epochs` in total, spreading trials over
+    /// `threads` workers, and re-scores them on the validation data.
+    fn train_trials(&self, trials: &mut [Trial], epochs: usize) {
+        if trials.is_empty() {
+            return;
+        }
+        let chunk_size = trials.len().div_ceil(self.threads.max(1));
+
+        thread::scope(|scope| {
+            for chunk in trials.chunks_mut(chunk_size) {
+                scope.spawn(move || {
+                    for trial in chunk {
+                        self.train_trial(trial, epochs);
+                    }
+                });
+            }
+        });
+    }
+
+    fn train_trial(&self, trial: &mut Trial, epochs: usize) {
+        let config = &trial.result.config;
+        let mut context = TrainingContext::new();
+        while trial.result.epochs < epochs {
+            trial.model.train_epoch_batched(&mut context, self.train_data, config.learning_rate, config.batch_size);
+            trial.result.epochs += 1;
+        }
+
+        let outputs = trial.model.forward_all(self.validation_data);
+        let targets = metrics::targets(self.validation_data);
+        let total_loss: f64 = outputs.iter().zip(&targets).map(|(o, t)| trial.model.loss.compute(o, t)).sum();
+        trial.result.validation_accuracy = metrics::accuracy(&outputs, &targets);
+        trial.result.validation_loss = total_loss / targets.len().max(1) as f64;
+    }
+}
+
+/// Writes one CSV row per trial, in the given order.
+pub fn write_results(results: &[TrialResult], path: impl AsRef<Path>) -> io::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record([
+        "rank",
+        "learning_rate",
+        "hidden_layers",
+        "activation",
+        "batch_size",
+        "weight_decay",
+        "epochs",
+        "validation_accuracy",
+        "validation_loss",
+    ])?;
+    for (rank, result) in results.iter().enumerate() {
+        let config = &result.config;
+        writer.write_record([
+            (rank + 1).to_string(),
+            config.learning_rate.to_string(),
+            config.hidden_layers_label(),
+            format!("{:?}", config.activation),
+            config.batch_size.to_string(),
+            config.weight_decay.to_string(),
+            result.epochs.to_string(),
+            result.validation_accuracy.to_string(),
+            result.validation_loss.to_string(),
+        ])?;
+    }
+    writer.flush()
+}
+
+/// Writes the configuration of `best` as `key = value` lines.
+pub fn write_best_config(best: &TrialResult, path: impl AsRef<Path>) -> io::Result<()> {
+    let config = &best.config;
+    let contents = format!(
+        "learning_rate = {}\nhidden_layers = {}\nactivation = {:?}\nbatch_size = {}\nweight_decay = {}\nepochs = {}\nvalidation_accuracy = {}\nvalidation_loss = {}\n",
+        config.learning_rate,
+        config.hidden_layers_label(),
+        config.activation,
+        config.batch_size,
+        config.weight_decay,
+        best.epochs,
+        best.validation_accuracy,
+        best.validation_loss,
+    );
+    std::fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn space() -> SearchSpace {
+        SearchSpace {
+            learning_rates: vec![0.5, 0.001],
+            hidden_layers: vec![vec![4], vec![3, 2]],
+            activations: vec![Activation::Sigmoid],
+            batch_sizes: vec![1, 2],
+            weight_decays: vec![0.0],
+        }
+    }
+
+    // Class 1 when the first feature is large
+    fn data() -> Vec<(Vec<f64>, Vec<f64>)> {
+        (0..40)
+            .map(|i| {
+                let x = (i % 10) as f64 / 10.0;
+                (vec![x, 1.0 - x], vec![if x > 0.45 { 1.0 } else { 0.0 }])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_grid_covers_every_combination() {
+        let configs = space().grid();
+        assert_eq!(configs.len(), 8);
+
+        let model = configs[3].build_model(2, 1, &mut rand::rng());
+        assert_eq!(model.layers.len(), 3);
+        assert_eq!(model.input_width(), 2);
+    }
+
+    #[test]
+    fn test_grid_search_ranks_trials() {
+        let data = data();
+        let search = Search::new(space(), &data, &data, RunSeed(1)).unwrap().with_threads(3);
+        let results = search.run(&Strategy::Grid { epochs: 2 });
+
+        assert_eq!(results.len(), 8);
+        assert!(results.iter().all(|r| r.epochs == 2));
+        assert!(results.windows(2).all(|w| w[0].validation_loss <= w[1].validation_loss));
+
+        let search = search.with_ranking(Ranking::ValidationAccuracy);
+        let results = search.run(&Strategy::Grid { epochs: 2 });
+        assert!(results.windows(2).all(|w| w[0].validation_accuracy >= w[1].validation_accuracy));
+    }
+
+    #[test]
+    fn test_successive_halving_budgets() {
+        let data = data();
+        let search = Search::new(space(), &data, &data, RunSeed(2)).unwrap().with_threads(2);
+        let results = search.run(&Strategy::SuccessiveHalving { trials: 9, min_epochs: 1, reduction_factor: 3 });
+
+        // 9 trials at 1 epoch, 3 continue to 3 epochs, 1 reaches 9
+        let mut epochs: Vec<usize> = results.iter().map(|r| r.epochs).collect();
+        epochs.sort();
+        assert_eq!(epochs, vec![1, 1, 1, 1, 1, 1, 3, 3, 9]);
+    }
+
+    #[test]
+    fn test_hyperband_respects_max_epochs() {
+        let data = data();
+        let search = Search::new(space(), &data, &data, RunSeed(3)).unwrap().with_threads(4);
+        let results = search.run(&Strategy::Hyperband { max_epochs: 4, reduction_factor: 2 });
+
+        assert!(!results.is_empty());
+        assert!(results.iter().all(|r| r.epochs >= 1 && r.epochs <= 4));
+        assert!(results.iter().any(|r| r.epochs == 4));
+    }
+
+    #[test]
+    fn test_invalid_space_is_rejected() {
+        let data = data();
+        let empty = SearchSpace { batch_sizes: Vec::new(), ..space() };
+        assert_eq!(
+            Search::new(empty, &data, &data, RunSeed(4)).err(),
+            Some(SearchError::NoCandidates { parameter: "batch_size" })
+        );
+
+        let zero_width = SearchSpace { hidden_layers: vec![vec![4], vec![3, 0]], ..space() };
+        assert_eq!(
+            zero_width.validate(),
+            Err(SearchError::InvalidCandidate { parameter: "hidden_layers", value: "[3, 0]".to_string() })
+        );
+    }
+
+    #[test]
+    fn test_write_results() {
+        let result = TrialResult {
+            config: space().grid().remove(0),
+            epochs: 3,
+            validation_accuracy: 0.9,
+            validation_loss: 0.1,
+        };
+        let dir = std::env::temp_dir();
+        let results_path = dir.join("search_results.csv");
+        let best_path = dir.join("search_best.txt");
+
+        write_results(std::slice::from_ref(&result), results_path.to_str().unwrap()).unwrap();
+        write_best_config(&result, best_path.to_str().unwrap()).unwrap();
+
+        let table = std::fs::read_to_string(&results_path).unwrap();
+        assert!(table.lines().nth(1).unwrap().starts_with("1,0.5,4,Sigmoid,1,0,3,0.9,0.1"));
+        let best = std::fs::read_to_string(&best_path).unwrap();
+        assert!(best.contains("hidden_layers = 4\n"));
+
+        std::fs::remove_file(results_path).unwrap();
+        std::fs::remove_file(best_path).unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Environment variable read by `RunSeed::requested`.
+pub const SEED_VAR: &str = "SEED";
+
+/// Seed for a whole run. Every random choice (data split, initialization,
+/// per-epoch shuffling, search sampling) draws from a stream derived from
+/// it, so two runs with the same seed produce bit-identical models.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunSeed(pub u64);
+
+impl RunSeed {
+    pub fn random() -> Self {
+        Self(rand::rng().random())
+    }
+
+    /// The seed in `SEED`, if it is set to a number.
+    pub fn requested() -> Option<Self> {
+        std::env::var(SEED_VAR).ok().and_then(|value| value.trim().parse().ok()).map(Self)
+    }
+
+    /// Generator for one named stream, e.g. `("shuffle", epoch)`. Streams
+    /// are independent of each other, so adding a new consumer does not
+    /// change the numbers seen by existing ones.
+    pub fn rng(&self, stream: &str, index: u64) -> StdRng {
+        // FNV-1a over the stream name, then a SplitMix64 finalizer
+        let name = stream.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+        let mut z = self.0 ^ name ^ index.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        StdRng::seed_from_u64(z ^ (z >> 31))
+    }
+}
+
+impl fmt::Display for RunSeed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::seq::SliceRandom;
+    use crate::data::dataset::Dataset;
+    use crate::ml::activation::Activation;
+    use crate::ml::layer::Layer;
+    use crate::ml::model::Model;
+    use crate::ml::training::TrainingContext;
+
+    fn run(seed: RunSeed) -> Model {
+        let rows: Vec<Vec<f64>> = (0..40)
+            .map(|i| vec![i as f64 / 40.0, (i % 7) as f64 / 7.0, if i % 3 == 0 { 1.0 } else { 0.0 }])
+            .collect();
+        let dataset = Dataset::from_rows(rows, 1, 0.75, &mut seed.rng("split", 0)).unwrap();
+
+        let mut init = seed.rng("init", 0);
+        let hidden = Layer::random(2, 4, Activation::ReLU, &mut init);
+        let output = Layer::random(4, 1, Activation::Sigmoid, &mut init);
+        let mut model = Model::new(2, vec![hidden, output]).unwrap();
+
+        let mut train_data = dataset.train_data.clone();
+        let mut context = TrainingContext::new();
+        for epoch in 0..5 {
+            train_data.shuffle(&mut seed.rng("shuffle", epoch));
+            model.train_epoch(&mut context, &train_data, 0.5);
+        }
+        model
+    }
+
+    fn parameter_bits(model: &Model) -> Vec<u64> {
+        model
+            .layers
+            .iter()
+            .flat_map(|layer| &layer.perceptrons)
+            .flat_map(|perceptron| perceptron.weights.iter().chain(std::iter::once(&perceptron.bias)))
+            .map(|value| value.to_bits())
+            .collect()
+    }
+
+    #[test]
+    fn test_same_seed_gives_identical_models() {
+        let first = parameter_bits(&run(RunSeed(7)));
+        assert_eq!(first, parameter_bits(&run(RunSeed(7))));
+        assert_ne!(first, parameter_bits(&run(RunSeed(8))));
+    }
+
+    #[test]
+    fn test_streams_are_independent() {
+        let seed = RunSeed(7);
+        let a: u64 = seed.rng("shuffle", 0).random();
+        assert_eq!(a, seed.rng("shuffle", 0).random::<u64>());
+        assert_ne!(a, seed.rng("shuffle", 1).random::<u64>());
+        assert_ne!(a, seed.rng("init", 0).random::<u64>());
+    }
+}
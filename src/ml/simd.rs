@@ -0,0 +1,358 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Implementation behind the dot product, axpy and activation kernels.
+///
+/// The AVX2 kernels use fused multiply-adds and sum in a different order,
+/// so they agree with the scalar ones to within rounding, not bit for bit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kernel {
+    Scalar,
+    /// AVX2 with FMA, 4 lanes of `f64`. Only on x86_64; on a CPU without
+    /// both features the methods fall back to the scalar kernels.
+    Avx2,
+}
+
+/// 0 until the first call to `Kernel::current`, then the kernel index + 1.
+static SELECTED: AtomicU8 = AtomicU8::new(0);
+
+impl Kernel {
+    /// Fastest kernel the CPU supports.
+    pub fn detect() -> Self {
+        if avx2_supported() { Kernel::Avx2 } else { Kernel::Scalar }
+    }
+
+    /// Kernel used by the free functions of this module: the detected one
+    /// unless overridden with `Kernel::select`.
+    pub fn current() -> Self {
+        match SELECTED.load(Ordering::Relaxed) {
+            1 => Kernel::Scalar,
+            2 => Kernel::Avx2,
+            _ => {
+                let kernel = Self::detect();
+                Self::select(kernel);
+                kernel
+            }
+        }
+    }
+
+    /// Overrides the kernel for the whole process, e.g. to compare against
+    /// the scalar path. A kernel the CPU lacks falls back to `Scalar`.
+    pub fn select(kernel: Kernel) {
+        let kernel = if kernel == Self::detect() { kernel } else { Kernel::Scalar };
+        SELECTED.store(kernel as u8 + 1, Ordering::Relaxed);
+    }
+
+    /// Sum of `a[i] * b[i]`. Panics if the slices differ in length.
+    pub fn dot(self, a: &[f64], b: &[f64]) -> f64 {
+        assert_eq!(a.len(), b.len(), "dot product of slices with different lengths");
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: the guard checked that the CPU supports AVX2 and FMA
+            Kernel::Avx2 if avx2_supported() => unsafe { avx2::dot(a, b) },
+            _ => scalar::dot(a, b),
+        }
+    }
+
+    /// `y[i] += alpha * x[i]`. Panics if the slices differ in length.
+    pub fn axpy(self, alpha: f64, x: &[f64], y: &mut [f64]) {
+        assert_eq!(x.len(), y.len(), "axpy of slices with different lengths");
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: as in `dot`
+            Kernel::Avx2 if avx2_supported() => unsafe { avx2::axpy(alpha, x, y) },
+            _ => scalar::axpy(alpha, x, y),
+        }
+    }
+
+    pub fn relu(self, values: &mut [f64]) {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: as in `dot`
+            Kernel::Avx2 if avx2_supported() => unsafe { avx2::relu(values) },
+            _ => scalar::relu(values),
+        }
+    }
+
+    pub fn sigmoid(self, values: &mut [f64]) {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: as in `dot`
+            Kernel::Avx2 if avx2_supported() => unsafe { avx2::sigmoid(values) },
+            _ => scalar::sigmoid(values),
+        }
+    }
+}
+
+/// Whether this CPU has AVX2 and FMA. The standard library caches the
+/// answer, so checking it on every kernel call is cheap.
+fn avx2_supported() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+pub fn dot(a: &[f64], b: &[f64]) -> f64 {
+    Kernel::current().dot(a, b)
+}
+
+pub fn axpy(alpha: f64, x: &[f64], y: &mut [f64]) {
+    Kernel::current().axpy(alpha, x, y)
+}
+
+pub fn relu(values: &mut [f64]) {
+    Kernel::current().relu(values)
+}
+
+pub fn sigmoid(values: &mut [f64]) {
+    Kernel::current().sigmoid(values)
+}
+
+mod scalar {
+    pub fn dot(a: &[f64], b: &[f64]) -> f64 {
+        a.iter().zip(b).map(|(a, b)| a * b).sum()
+    }
+
+    pub fn axpy(alpha: f64, x: &[f64], y: &mut [f64]) {
+        for (y, x) in y.iter_mut().zip(x) {
+            *y += alpha * x;
+        }
+    }
+
+    pub fn relu(values: &mut [f64]) {
+        for value in values {
+            *value = value.max(0.0);
+        }
+    }
+
+    pub fn sigmoid(values: &mut [f64]) {
+        for value in values {
+            *value = 1.0 / (1.0 + (-*value).exp());
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+    use super::scalar;
+
+    const LANES: usize = 4;
+
+    #[target_feature(enable = "avx2,fma")]
+    pub fn dot(a: &[f64], b: &[f64]) -> f64 {
+        let len = a.len().min(b.len());
+        let end = len - len % (2 * LANES);
+        let (mut sum0, mut sum1) = (_mm256_setzero_pd(), _mm256_setzero_pd());
+        for i in (0..end).step_by(2 * LANES) {
+            // SAFETY: i + 8 <= end <= len of both slices
+            unsafe {
+                let a0 = _mm256_loadu_pd(a.as_ptr().add(i));
+                let b0 = _mm256_loadu_pd(b.as_ptr().add(i));
+                let a1 = _mm256_loadu_pd(a.as_ptr().add(i + LANES));
+                let b1 = _mm256_loadu_pd(b.as_ptr().add(i + LANES));
+                sum0 = _mm256_fmadd_pd(a0, b0, sum0);
+                sum1 = _mm256_fmadd_pd(a1, b1, sum1);
+            }
+        }
+        horizontal_sum(_mm256_add_pd(sum0, sum1)) + scalar::dot(&a[end..len], &b[end..len])
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    pub fn axpy(alpha: f64, x: &[f64], y: &mut [f64]) {
+        let len = x.len().min(y.len());
+        let end = len - len % LANES;
+        let alpha_v = _mm256_set1_pd(alpha);
+        for i in (0..end).step_by(LANES) {
+            // SAFETY: i + 4 <= end <= len of both slices
+            unsafe {
+                let x_v = _mm256_loadu_pd(x.as_ptr().add(i));
+                let y_v = _mm256_loadu_pd(y.as_ptr().add(i));
+                _mm256_storeu_pd(y.as_mut_ptr().add(i), _mm256_fmadd_pd(alpha_v, x_v, y_v));
+            }
+        }
+        scalar::axpy(alpha, &x[end..len], &mut y[end..len]);
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    pub fn relu(values: &mut [f64]) {
+        let end = values.len() - values.len() % LANES;
+        let zero = _mm256_setzero_pd();
+        for i in (0..end).step_by(LANES) {
+            // SAFETY: i + 4 <= end <= values.len()
+            unsafe {
+                let v = _mm256_loadu_pd(values.as_ptr().add(i));
+                // maxpd returns its second operand for NaN, like f64::max
+                _mm256_storeu_pd(values.as_mut_ptr().add(i), _mm256_max_pd(v, zero));
+            }
+        }
+        scalar::relu(&mut values[end..]);
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    pub fn sigmoid(values: &mut [f64]) {
+        let end = values.len() - values.len() % LANES;
+        let one = _mm256_set1_pd(1.0);
+        for i in (0..end).step_by(LANES) {
+            // SAFETY: i + 4 <= end <= values.len()
+            unsafe {
+                let v = _mm256_loadu_pd(values.as_ptr().add(i));
+                let e = exp(_mm256_sub_pd(_mm256_setzero_pd(), v));
+                _mm256_storeu_pd(values.as_mut_ptr().add(i), _mm256_div_pd(one, _mm256_add_pd(one, e)));
+            }
+        }
+        scalar::sigmoid(&mut values[end..]);
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    fn horizontal_sum(v: __m256d) -> f64 {
+        let pair = _mm_add_pd(_mm256_castpd256_pd128(v), _mm256_extractf128_pd(v, 1));
+        _mm_cvtsd_f64(_mm_add_sd(pair, _mm_unpackhi_pd(pair, pair)))
+    }
+
+    /// `e^x` as `2^k * e^r` with `|r| <= ln(2)/2` and a degree-12 Taylor
+    /// polynomial for `e^r`, accurate to a few ulp. Inputs are clamped to
+    /// the range where `2^k` is a normal number; NaN propagates.
+    #[target_feature(enable = "avx2,fma")]
+    fn exp(x: __m256d) -> __m256d {
+        const LN_2_HI: f64 = 6.931_471_803_691_238e-1;
+        const LN_2_LO: f64 = 1.908_214_929_270_587_7e-10;
+        // Adding 2^52 + 1023 leaves k + 1023 in the low mantissa bits
+        const EXPONENT_MAGIC: f64 = 4_503_599_627_371_519.0;
+
+        // Constant first so that maxpd/minpd pass NaN through
+        let x = _mm256_min_pd(_mm256_set1_pd(709.0), _mm256_max_pd(_mm256_set1_pd(-708.0), x));
+        let k = _mm256_round_pd(
+            _mm256_mul_pd(x, _mm256_set1_pd(std::f64::consts::LOG2_E)),
+            _MM_FROUND_TO_NEAREST_INT | _MM_FROUND_NO_EXC,
+        );
+        let r = _mm256_fnmadd_pd(k, _mm256_set1_pd(LN_2_HI), x);
+        let r = _mm256_fnmadd_pd(k, _mm256_set1_pd(LN_2_LO), r);
+
+        let mut polynomial = _mm256_set1_pd(1.0 / 479_001_600.0);
+        let mut factorial = 479_001_600.0;
+        for n in (1..12).rev() {
+            factorial /= (n + 1) as f64;
+            polynomial = _mm256_fmadd_pd(polynomial, r, _mm256_set1_pd(1.0 / factorial));
+        }
+        polynomial = _mm256_fmadd_pd(polynomial, r, _mm256_set1_pd(1.0));
+
+        let exponent = _mm256_castpd_si256(_mm256_add_pd(k, _mm256_set1_pd(EXPONENT_MAGIC)));
+        let scale = _mm256_castsi256_pd(_mm256_slli_epi64(exponent, 52));
+        _mm256_mul_pd(polynomial, scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    fn random_vec(rng: &mut StdRng, len: usize, range: f64) -> Vec<f64> {
+        (0..len).map(|_| rng.random_range(-range..range)).collect()
+    }
+
+    #[test]
+    fn test_kernels_match_scalar() {
+        let kernel = Kernel::detect();
+        let mut rng = StdRng::seed_from_u64(0);
+        // Lengths around the lane and unroll boundaries, plus spambase's 57
+        for len in [0, 1, 3, 4, 5, 7, 8, 9, 16, 31, 57, 100] {
+            let a = random_vec(&mut rng, len, 1.0);
+            let b = random_vec(&mut rng, len, 1.0);
+            let magnitude: f64 = a.iter().zip(&b).map(|(a, b)| (a * b).abs()).sum();
+            let error = (kernel.dot(&a, &b) - Kernel::Scalar.dot(&a, &b)).abs();
+            assert!(error <= 1e-14 * magnitude.max(1.0), "dot len {}: {}", len, error);
+
+            let (mut expected, mut found) = (b.clone(), b.clone());
+            Kernel::Scalar.axpy(-0.3, &a, &mut expected);
+            kernel.axpy(-0.3, &a, &mut found);
+            for (expected, found) in expected.iter().zip(&found) {
+                assert!((expected - found).abs() <= 1e-15);
+            }
+
+            let inputs = random_vec(&mut rng, len, 40.0);
+            for activation in [Kernel::relu, Kernel::sigmoid] {
+                let (mut expected, mut found) = (inputs.clone(), inputs.clone());
+                activation(Kernel::Scalar, &mut expected);
+                activation(kernel, &mut found);
+                for (expected, found) in expected.iter().zip(&found) {
+                    assert!((expected - found).abs() <= 1e-15 * expected.abs().max(1e-300), "{} vs {}", expected, found);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sigmoid_edge_cases() {
+        let kernel = Kernel::detect();
+        let mut values = vec![0.0, -800.0, 800.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1e-3, -1e-3];
+        kernel.sigmoid(&mut values);
+
+        assert_eq!(values[0], 0.5);
+        assert!(values[1] < 1e-300);
+        assert_eq!(values[2], 1.0);
+        assert!(values[3].is_nan());
+        assert_eq!(values[4], 1.0);
+        assert!(values[5] < 1e-300);
+        assert!((values[6] + values[7] - 1.0).abs() < 1e-15);
+    }
+
+    #[test]
+    #[should_panic(expected = "different lengths")]
+    fn test_dot_length_mismatch_panics() {
+        dot(&[1.0, 2.0], &[1.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "different lengths")]
+    fn test_axpy_length_mismatch_panics() {
+        axpy(1.0, &[1.0], &mut [1.0, 2.0]);
+    }
+
+    #[test]
+    #[ignore = "benchmark: cargo test --release simd -- --ignored --nocapture"]
+    fn bench_spambase_epoch() {
+        use std::time::{Duration, Instant};
+        use crate::data::dataset::Dataset;
+        use crate::ml::activation::Activation;
+        use crate::ml::layer::Layer;
+        use crate::ml::model::Model;
+        use crate::ml::seed::RunSeed;
+        use crate::ml::training::TrainingContext;
+
+        let seed = RunSeed(0);
+        let mut dataset = Dataset::load_data("spambase/spambase.data", 0.8, &mut seed.rng("split", 0)).unwrap();
+        dataset.normalize();
+
+        let time_epochs = |kernel: Kernel| -> (Duration, f64) {
+            Kernel::select(kernel);
+            let mut rng = seed.rng("init", 0);
+            let mut model = Model::new(57, vec![
+                Layer::random(57, 32, Activation::ReLU, &mut rng),
+                Layer::random(32, 16, Activation::ReLU, &mut rng),
+                Layer::random(16, 1, Activation::Sigmoid, &mut rng),
+            ]).unwrap();
+            let mut context = TrainingContext::new();
+            let started = Instant::now();
+            let mut loss = 0.0;
+            for _ in 0..5 {
+                loss = model.train_epoch(&mut context, &dataset.train_data, 0.01);
+            }
+            (started.elapsed() / 5, loss)
+        };
+
+        let (scalar, scalar_loss) = time_epochs(Kernel::Scalar);
+        let (detected, detected_loss) = time_epochs(Kernel::detect());
+        Kernel::select(Kernel::detect());
+
+        println!("scalar: {:?}/epoch, {:?}: {:?}/epoch, speedup {:.2}x", scalar, Kernel::detect(), detected,
+            scalar.as_secs_f64() / detected.as_secs_f64());
+        assert!((scalar_loss - detected_loss).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt;
+use crate::ml::activation::Activation;
+use crate::ml::layer::Layer;
+use crate::ml::model::Model;
+
+/// Mean, population standard deviation, minimum and maximum of a set of
+/// values. All zero for an empty set.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub mean: f64,
+    pub std: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Stats {
+    pub fn of<'a>(values: impl Iterator<Item = &'a f64> + Clone) -> Self {
+        let count = values.clone().count();
+        if count == 0 {
+            return Self::default();
+        }
+        let mean = values.clone().sum::<f64>() / count as f64;
+        let variance = values.clone().map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64;
+        let min = values.clone().copied().fold(f64::INFINITY, f64::min);
+        let max = values.copied().fold(f64::NEG_INFINITY, f64::max);
+        Self { mean, std: variance.sqrt(), min, max }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LayerSummary {
+    pub index: usize,
+    pub kind: &'static str,
+    pub activation: Activation,
+    pub inputs: usize,
+    pub outputs: usize,
+    /// Weights and biases, including weights fixed at zero by a pruning mask.
+    pub parameters: usize,
+    /// `parameters` minus the masked weights, or 0 for a frozen layer.
+    pub trainable_parameters: usize,
+    pub weights: Stats,
+    pub biases: Stats,
+}
+
+impl LayerSummary {
+    pub fn of(index: usize, layer: &Layer) -> Self {
+        let weights = layer.perceptrons.iter().flat_map(|perceptron| &perceptron.weights);
+        let biases = layer.perceptrons.iter().map(|perceptron| &perceptron.bias);
+        let parameters = weights.clone().count() + layer.perceptrons.len();
+        let masked = layer.mask.iter().flatten().flatten().filter(|keep| !**keep).count();
+
+        Self {
+            index,
+            kind: "Dense",
+            activation: layer.activation,
+            inputs: layer.input_width(),
+            outputs: layer.perceptrons.len(),
+            parameters,
+            trainable_parameters: if layer.trainable { parameters - masked } else { 0 },
+            weights: Stats::of(weights),
+            biases: Stats::of(biases),
+        }
+    }
+}
+
+/// Per-layer table of a model, see `Model::summary`.
+#[derive(Clone, Debug)]
+pub struct ModelSummary {
+    pub layers: Vec<LayerSummary>,
+    pub total_parameters: usize,
+    pub trainable_parameters: usize,
+    /// Bytes used by the `f64` parameters and any pruning masks.
+    pub memory_bytes: usize,
+}
+
+impl ModelSummary {
+    pub fn of(model: &Model) -> Self {
+        let layers: Vec<LayerSummary> =
+            model.layers.iter().enumerate().map(|(index, layer)| LayerSummary::of(index, layer)).collect();
+        let total_parameters = layers.iter().map(|layer| layer.parameters).sum();
+        let mask_bytes: usize = model.layers.iter().flat_map(|layer| layer.mask.iter().flatten()).map(Vec::len).sum();
+
+        Self {
+            trainable_parameters: layers.iter().map(|layer| layer.trainable_parameters).sum(),
+            memory_bytes: total_parameters * std::mem::size_of::<f64>() + mask_bytes * std::mem::size_of::<bool>(),
+            total_parameters,
+            layers,
+        }
+    }
+
+    pub fn print(&self) {
+        print!("{}", self);
+    }
+}
+
+impl fmt::Display for ModelSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>5} {:<6} {:<8} {:>6} {:>6} {:>9} {:>9}  {:<33}  {:<33}",
+            "layer",
+            "type",
+            "act",
+            "in",
+            "out",
+            "params",
+            "trainable",
+            "weights mean/std/min/max",
+            "biases mean/std/min/max"
+        )?;
+        for layer in &self.layers {
+            writeln!(
+                f,
+                "{:>5} {:<6} {:<8} {:>6} {:>6} {:>9} {:>9}  {}  {}",
+                layer.index,
+                layer.kind,
+                layer.activation.name(),
+                layer.inputs,
+                layer.outputs,
+                layer.parameters,
+                layer.trainable_parameters,
+                format_stats(&layer.weights),
+                format_stats(&layer.biases),
+            )?;
+        }
+        writeln!(f, "total parameters: {} ({} trainable)", self.total_parameters, self.trainable_parameters)?;
+        writeln!(f, "memory: {:.1} KiB", self.memory_bytes as f64 / 1024.0)
+    }
+}
+
+fn format_stats(stats: &Stats) -> String {
+    format!("{:>+7.3} {:>7.3} {:>+7.3} {:>+7.3}", stats.mean, stats.std, stats.min, stats.max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::perceptron::Perceptron;
+
+    #[test]
+    fn test_summary_counts_and_stats() {
+        let hidden = Layer::new(vec![
+            Perceptron::new(vec![1.0, 3.0], 0.5),
+            Perceptron::new(vec![-1.0, 1.0], -0.5),
+        ], Activation::ReLU);
+        let mut output = Layer::new(vec![
+            Perceptron::new(vec![2.0, 0.0], 0.0),
+        ], Activation::Sigmoid);
+        output.mask = Some(vec![vec![true, false]]);
+        let model = Model::new(2, vec![hidden, output]).unwrap();
+
+        let summary = model.summary();
+        assert_eq!(summary.layers[0].parameters, 6);
+        assert_eq!((summary.layers[0].inputs, summary.layers[0].outputs), (2, 2));
+        assert_eq!(summary.layers[0].weights, Stats { mean: 1.0, std: 2f64.sqrt(), min: -1.0, max: 3.0 });
+        assert_eq!(summary.layers[0].biases.mean, 0.0);
+
+        assert_eq!(summary.total_parameters, 9);
+        assert_eq!(summary.trainable_parameters, 8);
+        assert_eq!(summary.memory_bytes, 9 * 8 + 2);
+
+        let table = summary.to_string();
+        assert_eq!(table.lines().count(), 5);
+        // Layer 1 has 3 parameters, one of them masked
+        let row: Vec<&str> = table.lines().nth(2).unwrap().split_whitespace().collect();
+        assert_eq!(&row[5..7], ["3", "2"]);
+        assert!(table.contains("total parameters: 9 (8 trainable)"));
+    }
+}
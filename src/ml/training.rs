@@ -0,0 +1,197 @@
+use std::ops::Range;
+use crate::ml::layer::{Layer, LayerCache};
+use crate::ml::loss::{ClassWeighting, Loss};
+
+/// Optimizer settings for a range of layers, e.g. a smaller learning rate
+/// for pretrained layers than for a new head.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParameterGroup {
+    pub layers: Range<usize>,
+    /// Multiplies the learning rate passed to the training call, so that
+    /// groups follow any schedule applied to it.
+    pub learning_rate_scale: f64,
+    pub weight_decay: f64,
+}
+
+impl ParameterGroup {
+    pub fn new(layers: Range<usize>, learning_rate_scale: f64, weight_decay: f64) -> Self {
+        Self { layers, learning_rate_scale, weight_decay }
+    }
+
+    /// One group per layer with scale `decay^(depth from the output)`: the
+    /// last layer trains at the full rate and earlier layers progressively
+    /// slower.
+    pub fn layerwise_decay(num_layers: usize, decay: f64, weight_decay: f64) -> Vec<Self> {
+        (0..num_layers)
+            .map(|layer| Self::new(layer..layer + 1, decay.powi((num_layers - 1 - layer) as i32), weight_decay))
+            .collect()
+    }
+}
+
+/// Learning-rate scale and weight decay for `layer`: from the first group
+/// containing it, else `1.0` and `default_weight_decay`.
+pub fn group_settings(groups: &[ParameterGroup], layer: usize, default_weight_decay: f64) -> (f64, f64) {
+    groups
+        .iter()
+        .find(|group| group.layers.contains(&layer))
+        .map_or((1.0, default_weight_decay), |group| (group.learning_rate_scale, group.weight_decay))
+}
+
+/// Backprop state for a stack of layers, kept apart from the parameters so
+/// that inference only needs a shared reference to the model.
+///
+/// Every buffer is reused across steps: after the first step on a given
+/// architecture, `forward`, `accumulate_sample` and `apply_gradients` do
+/// not allocate.
+#[derive(Clone, Default)]
+pub struct TrainingContext {
+    pub caches: Vec<LayerCache>,
+    output_gradients: Vec<f64>,
+}
+
+impl TrainingContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forward pass through `layers` that records what the backward pass
+    /// needs and returns the output. Grows or shrinks the caches to match
+    /// the layer count.
+    pub fn forward<'a>(&'a mut self, layers: &[Layer], input: &'a [f64]) -> &'a [f64] {
+        self.caches.resize_with(layers.len(), LayerCache::default);
+        for (index, layer) in layers.iter().enumerate() {
+            let (previous, rest) = self.caches.split_at_mut(index);
+            let input = previous.last().map_or(input, LayerCache::output);
+            layer.forward_cached(input, &mut rest[0]);
+        }
+        self.caches.last().map_or(input, LayerCache::output)
+    }
+
+    pub fn zero_gradients(&mut self, layers: &[Layer]) {
+        self.caches.resize_with(layers.len(), LayerCache::default);
+        for (layer, cache) in layers.iter().zip(&mut self.caches) {
+            layer.zero_gradients(cache);
+        }
+    }
+
+    /// Backpropagates `output_gradients` from the last forward pass, adding
+    /// to the stored gradients, and returns the gradients of the input.
+    pub fn accumulate_gradients<'a>(&'a mut self, layers: &[Layer], output_gradients: &'a [f64]) -> &'a [f64] {
+        backpropagate(&mut self.caches, layers, output_gradients);
+        self.caches.first().map_or(output_gradients, LayerCache::input_gradients)
+    }
+
+    /// Forward and backward pass for one sample, adding its gradients to the
+    /// stored ones. Returns the sample's weighted loss.
+    pub fn accumulate_sample(
+        &mut self,
+        layers: &[Layer],
+        input: &[f64],
+        target: &[f64],
+        loss: &Loss,
+        class_weighting: &ClassWeighting,
+    ) -> f64 {
+        self.forward(layers, input);
+        let output = self.caches.last().map_or(input, LayerCache::output);
+        let sample_loss = class_weighting.loss_and_gradients_into(loss, output, target, &mut self.output_gradients);
+        backpropagate(&mut self.caches, layers, &self.output_gradients);
+        sample_loss
+    }
+
+    /// Applies the stored gradients to `layers`, each at the learning rate
+    /// returned by `learning_rate` for its index.
+    pub fn apply_gradients(&self, layers: &mut [Layer], learning_rate: impl Fn(usize) -> f64) {
+        for (index, (layer, cache)) in layers.iter_mut().zip(&self.caches).enumerate() {
+            layer.apply_gradients(cache, learning_rate(index));
+        }
+    }
+}
+
+/// Runs every layer's backward pass from the last to the first, feeding
+/// each one the input gradients of the layer after it.
+fn backpropagate(caches: &mut [LayerCache], layers: &[Layer], output_gradients: &[f64]) {
+    for index in (0..layers.len().min(caches.len())).rev() {
+        let (current, next) = caches.split_at_mut(index + 1);
+        let gradients = next.first().map_or(output_gradients, LayerCache::input_gradients);
+        layers[index].accumulate_gradients(&mut current[index], gradients);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use super::*;
+    use crate::ml::activation::Activation;
+    use crate::ml::clipping::GradientClipping;
+    use crate::ml::model::Model;
+    use crate::ml::seed::RunSeed;
+
+    /// Counts the allocations made by the current thread, so that tests
+    /// running in parallel do not interfere.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            // SAFETY: forwarded unchanged to the system allocator
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            // SAFETY: as in `alloc`
+            unsafe { System.dealloc(ptr, layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            // SAFETY: as in `alloc`
+            unsafe { System.realloc(ptr, layout, new_size) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocations_during(f: impl FnOnce()) -> usize {
+        let before = ALLOCATIONS.with(Cell::get);
+        f();
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
+    #[test]
+    fn test_steady_state_training_does_not_allocate() {
+        let mut rng = RunSeed(3).rng("init", 0);
+        let mut model = Model::new(57, vec![
+            Layer::random(57, 32, Activation::ReLU, &mut rng),
+            Layer::random(32, 16, Activation::ReLU, &mut rng),
+            Layer::random(16, 1, Activation::Sigmoid, &mut rng),
+        ])
+        .unwrap()
+        .with_weight_decay(1e-4)
+        .with_gradient_clipping(GradientClipping::by_norm(1.0))
+        .with_parameter_groups(ParameterGroup::layerwise_decay(3, 0.5, 1e-4));
+        let data: Vec<(Vec<f64>, Vec<f64>)> =
+            (0..64).map(|i| ((0..57).map(|j| ((i * j) % 7) as f64 / 7.0).collect(), vec![(i % 2) as f64])).collect();
+
+        let mut context = TrainingContext::new();
+        // The first step sizes the buffers
+        model.train_step(&mut context, &data[0].0, &data[0].1, 0.01);
+
+        assert_eq!(allocations_during(|| { model.train_epoch(&mut context, &data, 0.01); }), 0);
+        assert_eq!(allocations_during(|| { model.train_epoch_batched(&mut context, &data, 0.01, 8); }), 0);
+
+        // Numeric checks inspect each step in place rather than copying the model
+        let mut checked = model.with_numeric_checks();
+        let mut steps = 0;
+        let allocations = allocations_during(|| {
+            checked.try_train_epoch_with(&mut context, &data, 0.01, 0, |_, _| steps += 1).unwrap();
+        });
+        assert_eq!(allocations, 0);
+        assert_eq!(steps, data.len());
+    }
+}